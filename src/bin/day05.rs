@@ -2,17 +2,43 @@
 
 use rayon::prelude::*;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::ops::Range;
 
 #[derive(Copy, Clone, Debug)]
 struct MapDataEntry {
     destination_range_start: u64,
     source_range_start: u64,
     range_length: u64,
+    // 1-based line in the input this entry came from
+    line_number: usize,
 }
 
-fn map_seed_to_location(mut seed: u64, maps: &HashMap<&str, (&str, Vec<MapDataEntry>)>) -> u64 {
+// every map in the almanac, keyed by source category
+type Maps<'a> = HashMap<&'a str, (&'a str, Vec<MapDataEntry>)>;
+
+impl MapDataEntry {
+    fn source_range(&self) -> Range<u64> {
+        self.source_range_start..(self.source_range_start + self.range_length)
+    }
+
+    fn shift(&self, value: u64) -> u64 {
+        value - self.source_range_start + self.destination_range_start
+    }
+
+    fn inverted(&self) -> MapDataEntry {
+        MapDataEntry {
+            destination_range_start: self.source_range_start,
+            source_range_start: self.destination_range_start,
+            range_length: self.range_length,
+            line_number: self.line_number,
+        }
+    }
+}
+
+fn map_seed_to_location(mut seed: u64, maps: &Maps) -> u64 {
     let mut current_layer = "seed";
     while current_layer != "location" {
         let (new_layer, map_data) = &maps[current_layer];
@@ -34,67 +60,783 @@ fn map_seed_to_location(mut seed: u64, maps: &HashMap<&str, (&str, Vec<MapDataEn
     seed
 }
 
-fn main() {
-    let file = File::open("data/day05.txt").unwrap();
-    let mut reader = BufReader::new(file);
-    let mut data = String::new();
-    let _ = reader
-        .read_to_string(&mut data)
-        .expect("failed to read data into string");
+// sorts and coalesces overlapping or touching ranges, so that the number of ranges per layer stays small
+fn merge_ranges(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.retain(|e| !e.is_empty());
+    ranges.sort_by_key(|e| e.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
 
-    let mut maps = HashMap::new();
+// one fragment of a range as it passes through a layer.
+// `entry` is the index of the MapDataEntry that moved it, or None if it passed through unchanged
+#[derive(Clone, Debug)]
+struct Flow {
+    source: Range<u64>,
+    destination: Range<u64>,
+    entry: Option<usize>,
+}
 
-    let Some(first_eol) = data.find("\n") else {
-        return;
-    };
-    let seeds = data[0..first_eol]
-        .split(" ")
-        .skip(1)
-        .map(|e| e.parse::<u64>().unwrap())
+fn flow_through_layer(ranges: &[Range<u64>], map_data: &[MapDataEntry]) -> Vec<Flow> {
+    let mut flows = Vec::new();
+    // pieces that haven't been claimed by any entry yet.
+    // entries are checked in order, same as map_seed_to_location, so the first matching entry wins
+    let mut unmapped = ranges.to_vec();
+    for (i, map_data_entry) in map_data.iter().enumerate() {
+        let source = map_data_entry.source_range();
+        let mut remaining = Vec::new();
+        for range in unmapped {
+            let start = range.start.max(source.start);
+            let end = range.end.min(source.end);
+            if start >= end {
+                remaining.push(range);
+                continue;
+            }
+            // split into the overlapping part (which gets shifted) and the parts hanging off either side
+            flows.push(Flow {
+                source: start..end,
+                destination: map_data_entry.shift(start)
+                    ..(map_data_entry.shift(start) + (end - start)),
+                entry: Some(i),
+            });
+            if range.start < start {
+                remaining.push(range.start..start);
+            }
+            if end < range.end {
+                remaining.push(end..range.end);
+            }
+        }
+        unmapped = remaining;
+    }
+    // anything not covered by an entry maps to itself
+    flows.extend(unmapped.into_iter().map(|e| Flow {
+        source: e.clone(),
+        destination: e,
+        entry: None,
+    }));
+    flows
+}
+
+fn map_ranges_through_layer(ranges: &[Range<u64>], map_data: &[MapDataEntry]) -> Vec<Range<u64>> {
+    merge_ranges(
+        flow_through_layer(ranges, map_data)
+            .into_iter()
+            .map(|e| e.destination)
+            .collect(),
+    )
+}
+
+fn map_ranges_to_location(ranges: &[Range<u64>], maps: &Maps) -> Vec<Range<u64>> {
+    let mut ranges = merge_ranges(ranges.to_vec());
+    let mut current_layer = "seed";
+    while current_layer != "location" {
+        let (new_layer, map_data) = &maps[current_layer];
+        ranges = map_ranges_through_layer(&ranges, map_data);
+        current_layer = new_layer;
+    }
+    ranges
+}
+
+// removes every value covered by `to_remove` from `ranges`
+fn subtract_ranges(ranges: &[Range<u64>], to_remove: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut out = ranges.to_vec();
+    for removed in to_remove {
+        let mut remaining = Vec::new();
+        for range in out {
+            if range.end <= removed.start || removed.end <= range.start {
+                remaining.push(range);
+                continue;
+            }
+            if range.start < removed.start {
+                remaining.push(range.start..removed.start);
+            }
+            if removed.end < range.end {
+                remaining.push(removed.end..range.end);
+            }
+        }
+        out = remaining;
+    }
+    merge_ranges(out)
+}
+
+// all values in the source category of this layer that land somewhere in `ranges`
+fn preimage_through_layer(ranges: &[Range<u64>], map_data: &[MapDataEntry]) -> Vec<Range<u64>> {
+    let mut preimage = Vec::new();
+    for (i, map_data_entry) in map_data.iter().enumerate() {
+        let inverted = map_data_entry.inverted();
+        let destination = inverted.source_range();
+        let mut candidates = Vec::new();
+        for range in ranges {
+            let start = range.start.max(destination.start);
+            let end = range.end.min(destination.end);
+            if start < end {
+                candidates.push(inverted.shift(start)..(inverted.shift(start) + (end - start)));
+            }
+        }
+        // a source value only goes through this entry if no earlier entry claimed it first
+        let claimed_earlier = map_data[..i]
+            .iter()
+            .map(|e| e.source_range())
+            .collect::<Vec<_>>();
+        preimage.extend(subtract_ranges(&candidates, &claimed_earlier));
+    }
+    // identity pass-through, for values that aren't the source of any entry
+    let all_sources = map_data
+        .iter()
+        .map(|e| e.source_range())
         .collect::<Vec<_>>();
-    println!("{seeds:?}");
+    preimage.extend(subtract_ranges(ranges, &all_sources));
+    merge_ranges(preimage)
+}
+
+// the categories visited when following maps forwards from `from` until reaching `to`, both ends included.
+// None if `to` can't be reached from `from`
+fn category_path<'a>(from: &'a str, to: &str, maps: &Maps<'a>) -> Option<Vec<&'a str>> {
+    let mut path = vec![from];
+    let mut current_layer = from;
+    while current_layer != to {
+        // bound the walk by the number of maps so that a cycle can't loop forever
+        if path.len() > maps.len() {
+            return None;
+        }
+        let (new_layer, _) = maps.get(current_layer)?;
+        path.push(new_layer);
+        current_layer = new_layer;
+    }
+    Some(path)
+}
+
+// values in `from` that end up in `ranges` of `to`, found by running the layers backwards.
+// None if `to` can't be reached from `from`
+fn preimage_between(
+    from: &str,
+    to: &str,
+    ranges: &[Range<u64>],
+    maps: &Maps,
+) -> Option<Vec<Range<u64>>> {
+    let path = category_path(from, to, maps)?;
+    let mut ranges = merge_ranges(ranges.to_vec());
+    for category in path.iter().rev().skip(1) {
+        ranges = preimage_through_layer(&ranges, &maps[category].1);
+    }
+    Some(ranges)
+}
+
+// a category along with the ranges reached in it
+type Hop<'a> = (&'a str, Vec<Range<u64>>);
+
+// maps ranges between any two categories, recording the ranges reached at every hop along the way.
+// if `to` comes before `from` in the chain, the layers are run backwards instead
+fn trace_between<'a>(
+    from: &'a str,
+    to: &'a str,
+    ranges: &[Range<u64>],
+    maps: &Maps<'a>,
+) -> Option<Vec<Hop<'a>>> {
+    let mut ranges = merge_ranges(ranges.to_vec());
+    let mut trace = vec![(from, ranges.clone())];
+    if let Some(path) = category_path(from, to, maps) {
+        for w in path.windows(2) {
+            ranges = map_ranges_through_layer(&ranges, &maps[w[0]].1);
+            trace.push((w[1], ranges.clone()));
+        }
+    } else {
+        let path = category_path(to, from, maps)?;
+        for w in path.windows(2).rev() {
+            ranges = preimage_through_layer(&ranges, &maps[w[0]].1);
+            trace.push((w[0], ranges.clone()));
+        }
+    }
+    Some(trace)
+}
+
+// singletons print as just the value
+fn format_ranges(ranges: &[Range<u64>]) -> String {
+    ranges
+        .iter()
+        .map(|e| {
+            if e.end - e.start == 1 {
+                e.start.to_string()
+            } else {
+                format!("{}..{}", e.start, e.end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// one linear segment of a piecewise map. values in start..end get `offset` added to them
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Piece {
+    start: u64,
+    end: u64,
+    offset: i64,
+}
+
+impl Piece {
+    fn apply(&self, value: u64) -> u64 {
+        value.checked_add_signed(self.offset).unwrap()
+    }
+
+    fn image(&self) -> Range<u64> {
+        self.apply(self.start)..self.apply(self.end)
+    }
+}
+
+// a whole layer (or chain of layers) flattened into sorted, contiguous pieces that cover 0..u64::MAX,
+// including the identity regions that aren't covered by any MapDataEntry
+#[derive(Clone, Debug)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    fn identity() -> Self {
+        PiecewiseMap {
+            pieces: vec![Piece {
+                start: 0,
+                end: u64::MAX,
+                offset: 0,
+            }],
+        }
+    }
+
+    fn from_layer(map_data: &[MapDataEntry]) -> Self {
+        // every entry boundary starts a new segment, and within a segment the first matching entry is constant
+        let mut boundaries = vec![0, u64::MAX];
+        for map_data_entry in map_data {
+            let source = map_data_entry.source_range();
+            boundaries.push(source.start);
+            boundaries.push(source.end);
+        }
+        boundaries.sort();
+        boundaries.dedup();
+
+        let pieces = boundaries
+            .windows(2)
+            .map(|w| {
+                let offset = map_data
+                    .iter()
+                    .find(|e| e.source_range().contains(&w[0]))
+                    .map(|e| e.destination_range_start as i64 - e.source_range_start as i64)
+                    .unwrap_or(0);
+                Piece {
+                    start: w[0],
+                    end: w[1],
+                    offset,
+                }
+            })
+            .collect();
+        PiecewiseMap { pieces }.simplified()
+    }
+
+    // joins neighbouring pieces that shift by the same amount
+    fn simplified(self) -> Self {
+        let mut pieces: Vec<Piece> = Vec::with_capacity(self.pieces.len());
+        for piece in self.pieces {
+            match pieces.last_mut() {
+                Some(last) if last.end == piece.start && last.offset == piece.offset => {
+                    last.end = piece.end
+                }
+                _ => pieces.push(piece),
+            }
+        }
+        PiecewiseMap { pieces }
+    }
+
+    // returns the map equivalent to applying self and then `next`
+    fn then(&self, next: &PiecewiseMap) -> Self {
+        let mut pieces = Vec::new();
+        for piece in &self.pieces {
+            let image = piece.image();
+            // next covers the whole domain, so the pieces overlapping the image partition it
+            let first = next.piece_index(image.start);
+            for next_piece in &next.pieces[first..] {
+                if next_piece.start >= image.end {
+                    break;
+                }
+                let start = image.start.max(next_piece.start);
+                let end = image.end.min(next_piece.end);
+                pieces.push(Piece {
+                    start: start.checked_add_signed(-piece.offset).unwrap(),
+                    end: end.checked_add_signed(-piece.offset).unwrap(),
+                    offset: piece.offset + next_piece.offset,
+                });
+            }
+        }
+        PiecewiseMap { pieces }.simplified()
+    }
+
+    fn piece_index(&self, value: u64) -> usize {
+        self.pieces.partition_point(|e| e.end <= value)
+    }
+
+    fn apply(&self, value: u64) -> u64 {
+        self.pieces[self.piece_index(value)].apply(value)
+    }
+
+    fn apply_to_ranges(&self, ranges: &[Range<u64>]) -> Vec<Range<u64>> {
+        let mut out = Vec::new();
+        for range in ranges {
+            for piece in &self.pieces[self.piece_index(range.start)..] {
+                if piece.start >= range.end {
+                    break;
+                }
+                let start = range.start.max(piece.start);
+                let end = range.end.min(piece.end);
+                out.push(piece.apply(start)..piece.apply(end));
+            }
+        }
+        merge_ranges(out)
+    }
+}
+
+impl Display for PiecewiseMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for piece in &self.pieces {
+            let image = piece.image();
+            writeln!(
+                f,
+                "{:>12}..{:<20} -> {:>12}..{:<20} ({:+})",
+                piece.start, piece.end, image.start, image.end, piece.offset
+            )?;
+        }
+        Ok(())
+    }
+}
 
-    // parse map chunks
-    let map_chunks = data[first_eol..].split("\n\n").skip(1).collect::<Vec<_>>();
-    for chunk in map_chunks {
-        let mut iter = chunk.split("\n");
+// composes every layer between two categories into a single map. None if `to` can't be reached from `from`
+fn compose_between(from: &str, to: &str, maps: &Maps) -> Option<PiecewiseMap> {
+    let path = category_path(from, to, maps)?;
+    let mut composed = PiecewiseMap::identity();
+    for category in &path[..path.len() - 1] {
+        composed = composed.then(&PiecewiseMap::from_layer(&maps[category].1));
+    }
+    Some(composed)
+}
 
-        // parse label into pieces
-        let Some(full_label) = iter.next() else {
+// parses the seed list and every map, keyed by source category.
+// also returns the line number of each map's header, for error reporting, and any lines that couldn't be parsed
+fn parse_almanac(
+    data: &str,
+) -> (
+    Vec<u64>,
+    Maps<'_>,
+    HashMap<&str, usize>,
+    Vec<AlmanacIssue<'_>>,
+) {
+    let mut lines = data.lines().enumerate().map(|(i, e)| (i + 1, e.trim()));
+    let mut issues = Vec::new();
+
+    let seeds = lines
+        .next()
+        .map(|(line_number, e)| {
+            let seeds = e
+                .strip_prefix("seeds:")
+                .map(|e| {
+                    e.split_whitespace()
+                        .map(|e| e.parse::<u64>())
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .and_then(Result::ok);
+            if seeds.is_none() {
+                issues.push(AlmanacIssue::MalformedSeeds { line_number });
+            }
+            seeds.unwrap_or_default()
+        })
+        .unwrap_or_default();
+
+    let mut maps = HashMap::new();
+    let mut header_lines = HashMap::new();
+    let mut current_source = None;
+    // entries under a repeated header are checked but not kept, the header itself is already an issue
+    let mut in_duplicate_map = false;
+    for (line_number, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(label) = line.strip_suffix(" map:") {
+            // parse label into pieces
+            let Some((source, target)) = label
+                .split_once("-to-")
+                .filter(|(source, target)| !source.is_empty() && !target.is_empty())
+            else {
+                issues.push(AlmanacIssue::MalformedHeader { line_number });
+                // don't let the entries that follow land in the previous map
+                current_source = None;
+                in_duplicate_map = false;
+                continue;
+            };
+            if let Some(first_line_number) = header_lines.get(source) {
+                issues.push(AlmanacIssue::DuplicateMap {
+                    line_number,
+                    first_line_number: *first_line_number,
+                });
+                current_source = None;
+                in_duplicate_map = true;
+                continue;
+            }
+            maps.insert(source, (target, Vec::new()));
+            header_lines.insert(source, line_number);
+            current_source = Some(source);
+            in_duplicate_map = false;
+            continue;
+        }
+
+        // parse MapDataEntry's
+        let Ok([destination_range_start, source_range_start, range_length]) = line
+            .split_whitespace()
+            .map(|e| e.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ())
+            .and_then(|e| <[u64; 3]>::try_from(e).map_err(|_| ()))
+        else {
+            issues.push(AlmanacIssue::MalformedEntry { line_number });
             continue;
         };
-        let (source, target) = {
-            let shortened = &full_label[0..full_label.len() - 5];
-            let mut split = shortened.split("-");
-            (split.next().unwrap(), split.nth(1).unwrap())
+        let Some(source) = current_source else {
+            if !in_duplicate_map {
+                issues.push(AlmanacIssue::EntryWithoutMap { line_number });
+            }
+            continue;
         };
+        maps.get_mut(source).unwrap().1.push(MapDataEntry {
+            destination_range_start,
+            source_range_start,
+            range_length,
+            line_number,
+        });
+    }
+    (seeds, maps, header_lines, issues)
+}
 
-        // parse MapDataEntry's
-        let data = iter
-            .map(|e| {
-                let mut tmp_dest = [0; 3];
-                e.split_whitespace()
-                    .map(|e| e.parse::<u64>().unwrap())
-                    .enumerate()
-                    .for_each(|(i, e)| {
-                        tmp_dest[i] = e;
-                    });
+#[derive(Debug)]
+enum AlmanacIssue<'a> {
+    // the chain from seed stops at a category with no map, before reaching location
+    MissingCategory {
+        category: &'a str,
+        referenced_at: usize,
+    },
+    Cycle {
+        categories: Vec<&'a str>,
+        line_number: usize,
+    },
+    DuplicateSource {
+        line_number: usize,
+        first_line_number: usize,
+    },
+    OverlappingSource {
+        line_number: usize,
+        first_line_number: usize,
+    },
+    ZeroLength {
+        line_number: usize,
+    },
+    MalformedSeeds {
+        line_number: usize,
+    },
+    MalformedHeader {
+        line_number: usize,
+    },
+    // a second map from the same source category
+    DuplicateMap {
+        line_number: usize,
+        first_line_number: usize,
+    },
+    MalformedEntry {
+        line_number: usize,
+    },
+    // an entry after a header that couldn't be parsed, or before any header at all
+    EntryWithoutMap {
+        line_number: usize,
+    },
+}
 
-                MapDataEntry {
-                    destination_range_start: tmp_dest[0],
-                    source_range_start: tmp_dest[1],
-                    range_length: tmp_dest[2],
+impl AlmanacIssue<'_> {
+    // overlaps and empty entries still resolve to a well defined (first match wins) answer, these don't
+    fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            AlmanacIssue::MissingCategory { .. }
+                | AlmanacIssue::Cycle { .. }
+                | AlmanacIssue::MalformedSeeds { .. }
+                | AlmanacIssue::MalformedHeader { .. }
+                | AlmanacIssue::DuplicateMap { .. }
+                | AlmanacIssue::MalformedEntry { .. }
+                | AlmanacIssue::EntryWithoutMap { .. }
+        )
+    }
+}
+
+impl Display for AlmanacIssue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlmanacIssue::MissingCategory {
+                category,
+                referenced_at,
+            } => write!(
+                f,
+                "line {referenced_at}: no map from {category}, location can't be reached"
+            ),
+            AlmanacIssue::Cycle {
+                categories,
+                line_number,
+            } => write!(
+                f,
+                "line {line_number}: maps form a cycle {} -> {}",
+                categories.join(" -> "),
+                categories[0]
+            ),
+            AlmanacIssue::DuplicateSource {
+                line_number,
+                first_line_number,
+            } => write!(
+                f,
+                "line {line_number}: source range duplicates line {first_line_number}, this entry is never used"
+            ),
+            AlmanacIssue::OverlappingSource {
+                line_number,
+                first_line_number,
+            } => write!(
+                f,
+                "line {line_number}: source range overlaps line {first_line_number}, which takes priority"
+            ),
+            AlmanacIssue::ZeroLength { line_number } => {
+                write!(f, "line {line_number}: entry has zero length")
+            }
+            AlmanacIssue::MalformedSeeds { line_number } => {
+                write!(f, "line {line_number}: expected `seeds:` followed by numbers")
+            }
+            AlmanacIssue::MalformedHeader { line_number } => {
+                write!(f, "line {line_number}: expected `<source>-to-<target> map:`")
+            }
+            AlmanacIssue::DuplicateMap {
+                line_number,
+                first_line_number,
+            } => write!(
+                f,
+                "line {line_number}: source category already has a map at line {first_line_number}"
+            ),
+            AlmanacIssue::MalformedEntry { line_number } => write!(
+                f,
+                "line {line_number}: expected three numbers, destination start, source start and length"
+            ),
+            AlmanacIssue::EntryWithoutMap { line_number } => {
+                write!(f, "line {line_number}: entry doesn't belong to any map")
+            }
+        }
+    }
+}
+
+fn validate_almanac<'a>(
+    maps: &Maps<'a>,
+    header_lines: &HashMap<&'a str, usize>,
+) -> Vec<AlmanacIssue<'a>> {
+    let mut issues = Vec::new();
+    // every source category, in the order their maps appear
+    let mut sources = maps.keys().copied().collect::<Vec<_>>();
+    sources.sort_by_key(|e| header_lines[e]);
+
+    // follow the chain from every category, so that cycles not reachable from seed are still found
+    let mut reported_cycles: Vec<Vec<&str>> = Vec::new();
+    for start in sources.iter().copied() {
+        let mut path = vec![start];
+        let mut current_layer = start;
+        while let Some((new_layer, _)) = maps.get(current_layer) {
+            if let Some(i) = path.iter().position(|e| e == new_layer) {
+                // rotate so the same cycle found from a different start compares equal
+                let mut cycle = path[i..].to_vec();
+                let min_i = (0..cycle.len()).min_by_key(|e| cycle[*e]).unwrap();
+                cycle.rotate_left(min_i);
+                if !reported_cycles.contains(&cycle) {
+                    issues.push(AlmanacIssue::Cycle {
+                        categories: cycle.clone(),
+                        line_number: header_lines[cycle[0]],
+                    });
+                    reported_cycles.push(cycle);
                 }
-            })
-            .collect::<Vec<_>>();
+                break;
+            }
+            path.push(new_layer);
+            current_layer = new_layer;
+        }
+    }
 
-        maps.insert(source, (target, data));
+    let mut current_layer = "seed";
+    let mut visited = vec![];
+    while current_layer != "location" && !visited.contains(&current_layer) {
+        visited.push(current_layer);
+        let Some((new_layer, _)) = maps.get(current_layer) else {
+            // blame the header that pointed here, or the seeds line if seed itself has no map
+            let referenced_at = maps
+                .iter()
+                .find(|(_, (target, _))| *target == current_layer)
+                .map(|(source, _)| header_lines[source])
+                .unwrap_or(1);
+            issues.push(AlmanacIssue::MissingCategory {
+                category: current_layer,
+                referenced_at,
+            });
+            break;
+        };
+        current_layer = new_layer;
     }
 
+    for source in sources.iter().copied() {
+        let map_data = &maps[source].1;
+        for (i, map_data_entry) in map_data.iter().enumerate() {
+            if map_data_entry.range_length == 0 {
+                issues.push(AlmanacIssue::ZeroLength {
+                    line_number: map_data_entry.line_number,
+                });
+                continue;
+            }
+            let range = map_data_entry.source_range();
+            // only report against the first earlier entry it collides with
+            let Some(earlier) = map_data[..i].iter().find(|e| {
+                let other = e.source_range();
+                range.start < other.end && other.start < range.end
+            }) else {
+                continue;
+            };
+            if earlier.source_range() == range {
+                issues.push(AlmanacIssue::DuplicateSource {
+                    line_number: map_data_entry.line_number,
+                    first_line_number: earlier.line_number,
+                });
+            } else {
+                issues.push(AlmanacIssue::OverlappingSource {
+                    line_number: map_data_entry.line_number,
+                    first_line_number: earlier.line_number,
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+// draws how the seed spans fragment on their way to location. each category in the chain is a column,
+// each live range is a bar in that column, and each fragment moved by a MapDataEntry is a band
+// between two columns, coloured by the entry that moved it. identity pass-through is grey
+fn range_flow_svg(spans: &[Range<u64>], maps: &Maps) -> String {
+    const COLUMN_SPACING: f64 = 220.0;
+    const BAR_WIDTH: f64 = 14.0;
+    const MARGIN: f64 = 40.0;
+    const PLOT_HEIGHT: f64 = 900.0;
+
+    let path = category_path("seed", "location", maps).unwrap();
+    let mut columns = vec![merge_ranges(spans.to_vec())];
+    let mut layer_flows = Vec::new();
+    for category in &path[..path.len() - 1] {
+        let flows = flow_through_layer(columns.last().unwrap(), &maps[category].1);
+        columns.push(merge_ranges(
+            flows.iter().map(|e| e.destination.clone()).collect(),
+        ));
+        layer_flows.push(flows);
+    }
+
+    let max_value = columns
+        .iter()
+        .flatten()
+        .map(|e| e.end)
+        .max()
+        .unwrap_or(1)
+        .max(1) as f64;
+    // rounded to a tenth of a pixel to keep the file small
+    let y = |value: u64| ((MARGIN + value as f64 / max_value * PLOT_HEIGHT) * 10.0).round() / 10.0;
+    let x = |column: usize| MARGIN + column as f64 * COLUMN_SPACING;
+    // keep tiny ranges visible
+    let height = |range: &Range<u64>| (y(range.end) - y(range.start)).max(1.0);
+
+    let width = 2.0 * MARGIN + (path.len() - 1) as f64 * COLUMN_SPACING + BAR_WIDTH;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        PLOT_HEIGHT + 2.0 * MARGIN
+    );
+    svg += "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n";
+
+    for (column, flows) in layer_flows.iter().enumerate() {
+        let (x0, x1) = (x(column) + BAR_WIDTH, x(column + 1));
+        let mid = (x0 + x1) / 2.0;
+        for flow in flows {
+            let colour = match flow.entry {
+                Some(i) => format!("hsl({}, 70%, 50%)", (i * 47) % 360),
+                None => "grey".to_string(),
+            };
+            let (s0, s1) = (
+                y(flow.source.start),
+                y(flow.source.start) + height(&flow.source),
+            );
+            let (d0, d1) = (
+                y(flow.destination.start),
+                y(flow.destination.start) + height(&flow.destination),
+            );
+            svg += &format!(
+                "<path d=\"M{x0},{s0} C{mid},{s0} {mid},{d0} {x1},{d0} L{x1},{d1} C{mid},{d1} {mid},{s1} {x0},{s1} Z\" fill=\"{colour}\" fill-opacity=\"0.45\"><title>{}..{} -> {}..{}{}</title></path>\n",
+                flow.source.start,
+                flow.source.end,
+                flow.destination.start,
+                flow.destination.end,
+                flow.entry
+                    .map(|e| format!(" (line {})", maps[path[column]].1[e].line_number))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    for (column, ranges) in columns.iter().enumerate() {
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>\n",
+            x(column),
+            MARGIN - 10.0,
+            path[column]
+        );
+        for range in ranges {
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{BAR_WIDTH}\" height=\"{}\" fill=\"black\"><title>{}..{}</title></rect>\n",
+                x(column),
+                y(range.start),
+                height(range),
+                range.start,
+                range.end
+            );
+        }
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+fn main() {
+    let file = File::open("data/day05.txt").unwrap();
+    let mut reader = BufReader::new(file);
+    let mut data = String::new();
+    let _ = reader
+        .read_to_string(&mut data)
+        .expect("failed to read data into string");
+
+    let (seeds, maps, header_lines, mut issues) = parse_almanac(&data);
+    println!("{seeds:?}");
+
     for map in &maps {
         println!("{map:?}");
     }
 
+    issues.extend(validate_almanac(&maps, &header_lines));
+    for issue in &issues {
+        println!("{issue}");
+    }
+    if issues.iter().any(|e| e.is_fatal()) {
+        println!("almanac is broken, refusing to solve");
+        return;
+    }
+
     let mut min_location_from_initial = u64::MAX;
 
     for v in seeds.clone() {
@@ -105,28 +847,147 @@ fn main() {
         }
     }
 
-    // TODO: optimize this solution to use ranges instead of individual seed values
+    // cross check the interval mapping against the single seed results, treating each seed as a span of length 1
+    let single_seed_ranges = seeds.iter().map(|e| *e..(*e + 1)).collect::<Vec<_>>();
+    let min_location_from_ranges = map_ranges_to_location(&single_seed_ranges, &maps)
+        .first()
+        .map_or(u64::MAX, |e| e.start);
+    assert_eq!(min_location_from_initial, min_location_from_ranges);
 
-    let mut min_location_in_spans = u64::MAX;
+    let mut spans = Vec::new();
     let mut iter = seeds.iter();
     while let Ok(window) = iter.next_chunk::<2>() {
-        // for v in *window[0]..(*window[0] + *window[1]) {
-        //     let location = map_seed_to_location(v, &maps);
-        //     if location < min_location_in_spans {
-        //         min_location_in_spans = location;
-        //         println!("(spans) new minimum location found: {min_location_in_spans}");
-        //     }
-        // }
-        let min_for_range = (*window[0]..(*window[0] + *window[1]))
-            .into_par_iter()
-            .map(|e| map_seed_to_location(e, &maps))
-            .min()
-            .unwrap();
-        if min_for_range < min_location_in_spans {
-            min_location_in_spans = min_for_range;
-            println!("(spans) new minimum location found: {min_location_in_spans}");
+        spans.push(*window[0]..(*window[0] + *window[1]));
+    }
+
+    // map whole spans through each layer at once, the minimum location is then just the start of the first range
+    let location_ranges = map_ranges_to_location(&spans, &maps);
+    println!(
+        "{} seed spans became {} location ranges",
+        spans.len(),
+        location_ranges.len()
+    );
+    let Some(min_location_in_spans) = location_ranges.first().map(|e| e.start) else {
+        println!("min location from single seeds is {min_location_from_initial}");
+        println!("no complete seed spans, needs a start and a length for each");
+        return;
+    };
+
+    if std::env::args().any(|e| e == "--brute-force") {
+        // old approach, checks every seed in every span individually. takes a while.
+        let mut brute_force_min = u64::MAX;
+        for span in &spans {
+            let min_for_range = span
+                .clone()
+                .into_par_iter()
+                .map(|e| map_seed_to_location(e, &maps))
+                .min()
+                .unwrap();
+            if min_for_range < brute_force_min {
+                brute_force_min = min_for_range;
+                println!("(spans) new minimum location found: {brute_force_min}");
+            }
+        }
+        assert_eq!(brute_force_min, min_location_in_spans);
+    }
+
+    // the whole chain as a single lookup table
+    let seed_to_location = compose_between("seed", "location", &maps).unwrap();
+    println!(
+        "seed to location composes into {} pieces:\n{seed_to_location}",
+        seed_to_location.pieces.len()
+    );
+    for seed in &seeds {
+        assert_eq!(
+            seed_to_location.apply(*seed),
+            map_seed_to_location(*seed, &maps)
+        );
+    }
+    assert_eq!(seed_to_location.apply_to_ranges(&spans), location_ranges);
+
+    // run the best location backwards, and check that every seed it finds maps forwards onto it again
+    let best_location = min_location_in_spans..(min_location_in_spans + 1);
+    let best_seeds = preimage_between(
+        "seed",
+        "location",
+        std::slice::from_ref(&best_location),
+        &maps,
+    )
+    .unwrap();
+    for range in &best_seeds {
+        assert_eq!(
+            map_seed_to_location(range.start, &maps),
+            min_location_in_spans
+        );
+        assert_eq!(
+            map_seed_to_location(range.end - 1, &maps),
+            min_location_in_spans
+        );
+    }
+    assert!(best_seeds
+        .iter()
+        .any(|e| spans.iter().any(|s| e.start < s.end && s.start < e.end)));
+    // and the same thing in bulk, the preimage of the location ranges has to cover every span
+    let seeds_for_locations =
+        preimage_between("seed", "location", &location_ranges, &maps).unwrap();
+    assert!(subtract_ranges(&spans, &seeds_for_locations).is_empty());
+
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|e| e == "--seeds-for") {
+        let start = args[i + 1].parse::<u64>().unwrap();
+        let end = args
+            .get(i + 2)
+            .and_then(|e| e.parse::<u64>().ok())
+            .unwrap_or(start + 1);
+        let location_range = start..end;
+        let seeds_for = preimage_between(
+            "seed",
+            "location",
+            std::slice::from_ref(&location_range),
+            &maps,
+        )
+        .unwrap();
+        println!("seeds landing in location {start}..{end}:");
+        for range in seeds_for {
+            println!("  {range:?}");
         }
     }
+    if let Some(i) = args.iter().position(|e| e == "--query") {
+        // --query <from> <to> <value or start..end>, e.g. --query soil humidity 81
+        let (from, to) = (args[i + 1].as_str(), args[i + 2].as_str());
+        let query = match args[i + 3].split_once("..") {
+            Some((start, end)) => start.parse::<u64>().unwrap()..end.parse::<u64>().unwrap(),
+            None => {
+                let value = args[i + 3].parse::<u64>().unwrap();
+                value..(value + 1)
+            }
+        };
+        match trace_between(from, to, std::slice::from_ref(&query), &maps) {
+            Some(trace) => {
+                println!(
+                    "{}",
+                    trace.iter().map(|e| e.0).collect::<Vec<_>>().join(" -> ")
+                );
+                for (category, ranges) in trace {
+                    println!("  {category:>12}: {}", format_ranges(&ranges));
+                }
+            }
+            None => println!("no path between {from} and {to}"),
+        }
+    }
+    if let Some(i) = args.iter().position(|e| e == "--svg") {
+        let path = args.get(i + 1).map(|e| e.as_str()).unwrap_or("day05.svg");
+        std::fs::write(path, range_flow_svg(&spans, &maps)).unwrap();
+        println!("wrote range flow to {path}");
+    }
+    if let Some(i) = args.iter().position(|e| e == "--compose") {
+        let (from, to) = (args[i + 1].as_str(), args[i + 2].as_str());
+        match compose_between(from, to, &maps) {
+            Some(composed) => println!("{from} to {to}:\n{composed}"),
+            None => println!("{to} can't be reached from {from}"),
+        }
+    }
+
     println!("min location from single seeds is {min_location_from_initial}");
     println!("min location from spans is {min_location_in_spans}");
 }
@@ -1,28 +1,308 @@
 #![feature(iter_intersperse)]
+use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::ops::RangeInclusive;
 
-fn ways(race_time_ms: u64, current_record_distance_mm: u64) -> u64 {
+// every hold time that beats the record, or None if the record can't be beaten
+fn ways(race_time_ms: u64, current_record_distance_mm: u64) -> Option<RangeInclusive<u64>> {
     // boat charges up speed at a rate of 1mm/1ms per ms
     // but every ms spent charging reduces time available to actually go
 
-    let discriminant =
-        ((race_time_ms * race_time_ms - 4 * current_record_distance_mm) as f32).sqrt();
+    // holding for h wins when h * (t - h) > d, i.e. h^2 - t*h + d < 0,
+    // so the winning holds lie strictly between the roots (t +- sqrt(t^2 - 4d)) / 2.
+    // u128 so that t^2 can't overflow
+    let (t, d) = (race_time_ms as u128, current_record_distance_mm as u128);
+    let discriminant = (t * t).checked_sub(4 * d)?;
+    let root = discriminant.isqrt();
 
-    let t0_start = ((race_time_ms as f32 - discriminant) / 2.0).floor() as u64;
-    let t1_end = ((race_time_ms as f32 + discriminant) / 2.0).ceil() as u64;
+    // root is the floored square root, so (t - root) / 2 is less than one step below the exact lower root.
+    // the first winning hold is either it or the one after
+    let mut first = (t - root) / 2;
+    if first * (t - first) <= d {
+        first += 1;
+    }
+    // distance is symmetric around t / 2
+    let last = t - first;
+
+    (first <= last).then_some(first as u64..=last as u64)
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Charge {
+    // mm/ms of speed gained per ms held
+    Linear(u64),
+    // speed as an arbitrary function of hold time. no closed form, so these always get scanned
+    Custom(fn(u64) -> u64),
+}
+
+// the rules for how a boat turns hold time into distance.
+// the puzzle's boat is `BoatModel::default()`, 1 mm/ms per ms held with no limits
+#[derive(Copy, Clone, Debug)]
+struct BoatModel {
+    charge: Charge,
+    // speed can't go above this, no matter how long the button is held
+    max_speed: Option<u64>,
+    // holding past this many ms adds no speed, but still eats into the race
+    charge_limit_ms: Option<u64>,
+    // ms lost at the start of each race, before charging begins
+    handicap_ms: u64,
+}
+
+impl Default for BoatModel {
+    fn default() -> Self {
+        BoatModel {
+            charge: Charge::Linear(1),
+            max_speed: None,
+            charge_limit_ms: None,
+            handicap_ms: 0,
+        }
+    }
+}
+
+impl BoatModel {
+    fn speed(&self, hold_ms: u64) -> u64 {
+        let charged_ms = hold_ms.min(self.charge_limit_ms.unwrap_or(u64::MAX));
+        let speed = match self.charge {
+            Charge::Linear(rate) => rate.saturating_mul(charged_ms),
+            Charge::Custom(f) => f(charged_ms),
+        };
+        speed.min(self.max_speed.unwrap_or(u64::MAX))
+    }
+
+    fn distance(&self, race_time_ms: u64, hold_ms: u64) -> u128 {
+        let moving_ms = race_time_ms
+            .saturating_sub(self.handicap_ms)
+            .saturating_sub(hold_ms);
+        self.speed(hold_ms) as u128 * moving_ms as u128
+    }
+
+    // every hold time that beats the record, as sorted, non overlapping ranges.
+    // with a linear charge it's always a single range, a cap or charge limit only flattens the top of the curve.
+    // a custom charge can dip though, e.g. |hold_ms| hold_ms % 10, and then the wins come in several pieces
+    fn winning_holds(
+        &self,
+        race_time_ms: u64,
+        current_record_distance_mm: u64,
+    ) -> Vec<RangeInclusive<u64>> {
+        match self.charge {
+            Charge::Linear(rate) => self.winning_holds_linear(
+                rate,
+                race_time_ms.saturating_sub(self.handicap_ms),
+                current_record_distance_mm,
+            ),
+            Charge::Custom(_) => self.winning_holds_scan(race_time_ms, current_record_distance_mm),
+        }
+    }
+
+    fn winning_holds_linear(
+        &self,
+        rate: u64,
+        race_time_ms: u64,
+        current_record_distance_mm: u64,
+    ) -> Vec<RangeInclusive<u64>> {
+        if rate == 0 {
+            return Vec::new();
+        }
+        // speed grows by `rate` per ms up to the hold `saturation_ms`, and is constant from then on
+        let saturation_ms = self
+            .charge_limit_ms
+            .unwrap_or(u64::MAX)
+            .min(self.max_speed.map(|e| e.div_ceil(rate)).unwrap_or(u64::MAX));
+
+        let mut holds = Vec::new();
+        // before saturating it's the usual quadratic, scaled by rate.
+        // rate * x > d is the same as x > d / rate (rounded down) when x is an integer
+        if let Some(quadratic) = ways(race_time_ms, current_record_distance_mm / rate) {
+            let last = (*quadratic.end()).min(saturation_ms.saturating_sub(1));
+            if saturation_ms > 0 && *quadratic.start() <= last {
+                holds.push(*quadratic.start()..=last);
+            }
+        }
+        // after saturating, distance drops linearly with every extra ms held
+        let saturated_speed = self.speed(saturation_ms);
+        if saturated_speed > 0 && saturation_ms <= race_time_ms {
+            let moving_ms_needed = current_record_distance_mm / saturated_speed + 1;
+            if let Some(last) = race_time_ms.checked_sub(moving_ms_needed) {
+                if saturation_ms <= last {
+                    // if there's a quadratic piece it ends right at saturation_ms - 1: holding on past the peak
+                    // loses before saturating, and saturated distance is no more than the quadratic one
+                    match holds.last_mut() {
+                        Some(previous) if *previous.end() + 1 == saturation_ms => {
+                            *previous = *previous.start()..=last
+                        }
+                        _ => holds.push(saturation_ms..=last),
+                    }
+                }
+            }
+        }
+        holds
+    }
+
+    fn winning_holds_scan(
+        &self,
+        race_time_ms: u64,
+        current_record_distance_mm: u64,
+    ) -> Vec<RangeInclusive<u64>> {
+        let mut holds: Vec<RangeInclusive<u64>> = Vec::new();
+        for hold_ms in 0..=race_time_ms {
+            if self.distance(race_time_ms, hold_ms) <= current_record_distance_mm as u128 {
+                continue;
+            }
+            match holds.last_mut() {
+                Some(previous) if *previous.end() + 1 == hold_ms => {
+                    *previous = *previous.start()..=hold_ms
+                }
+                _ => holds.push(hold_ms..=hold_ms),
+            }
+        }
+        holds
+    }
+}
+
+fn count_holds(holds: &[RangeInclusive<u64>]) -> u64 {
+    holds.iter().map(|e| 1 + e.end() - e.start()).sum()
+}
 
-    let mut t0 = t0_start;
-    while t0 * (race_time_ms - t0) <= current_record_distance_mm {
-        t0 += 1;
+struct SweepRow {
+    hold_ms: u64,
+    distance_mm: u128,
+    // how far past (or short of) the record this hold gets
+    margin_mm: i128,
+}
+
+// distance for every hold time in a race, plus the best hold and how much room there is around it
+struct RaceSweep {
+    race_time_ms: u64,
+    current_record_distance_mm: u64,
+    rows: Vec<SweepRow>,
+    optimal_hold_ms: u64,
+    optimal_distance_mm: u128,
+    winning_holds: Vec<RangeInclusive<u64>>,
+}
+
+impl RaceSweep {
+    // `max_rows` samples the table evenly for long races, None keeps every hold time.
+    // the optimum and the holds either side of each winning range's edges are always kept
+    fn new(
+        model: &BoatModel,
+        race_time_ms: u64,
+        current_record_distance_mm: u64,
+        max_rows: Option<u64>,
+    ) -> Self {
+        let (optimal_hold_ms, optimal_distance_mm) = (0..=race_time_ms)
+            .map(|e| (e, model.distance(race_time_ms, e)))
+            .fold((0, 0), |best, e| if e.1 > best.1 { e } else { best });
+        let winning_holds = model.winning_holds(race_time_ms, current_record_distance_mm);
+
+        let step = max_rows
+            .map(|e| (race_time_ms + 1).div_ceil(e.max(1)))
+            .unwrap_or(1);
+        let mut holds = (0..=race_time_ms)
+            .step_by(step as usize)
+            .collect::<Vec<_>>();
+        holds.push(race_time_ms);
+        holds.push(optimal_hold_ms);
+        for range in &winning_holds {
+            holds.extend([
+                range.start().saturating_sub(1),
+                *range.start(),
+                *range.end(),
+                (range.end() + 1).min(race_time_ms),
+            ]);
+        }
+        holds.sort();
+        holds.dedup();
+
+        let rows = holds
+            .into_iter()
+            .map(|hold_ms| {
+                let distance_mm = model.distance(race_time_ms, hold_ms);
+                SweepRow {
+                    hold_ms,
+                    distance_mm,
+                    margin_mm: distance_mm as i128 - current_record_distance_mm as i128,
+                }
+            })
+            .collect();
+
+        RaceSweep {
+            race_time_ms,
+            current_record_distance_mm,
+            rows,
+            optimal_hold_ms,
+            optimal_distance_mm,
+            winning_holds,
+        }
+    }
+
+    // the winning range the optimum sits in, i.e. how early or late the button can be released and still win
+    fn slack_window(&self) -> Option<&RangeInclusive<u64>> {
+        self.winning_holds
+            .iter()
+            .find(|e| e.contains(&self.optimal_hold_ms))
     }
 
-    let mut t1 = t1_end;
-    while t1 * (race_time_ms - t1) <= current_record_distance_mm {
-        t1 -= 1;
+    fn write_csv(&self, mut w: impl Write) -> std::io::Result<()> {
+        writeln!(w, "hold_ms,distance_mm,margin_mm,wins")?;
+        for row in &self.rows {
+            writeln!(
+                w,
+                "{},{},{},{}",
+                row.hold_ms,
+                row.distance_mm,
+                row.margin_mm,
+                row.margin_mm > 0
+            )?;
+        }
+        Ok(())
     }
+}
 
-    1 + t1 - t0
+impl Display for RaceSweep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "race of {}ms, record {}mm",
+            self.race_time_ms, self.current_record_distance_mm
+        )?;
+        write!(
+            f,
+            "optimal hold {}ms goes {}mm ({:+}mm)",
+            self.optimal_hold_ms,
+            self.optimal_distance_mm,
+            self.optimal_distance_mm as i128 - self.current_record_distance_mm as i128
+        )?;
+        match self.slack_window() {
+            Some(window) => writeln!(
+                f,
+                ", slack window {window:?} (-{}ms/+{}ms)",
+                self.optimal_hold_ms - window.start(),
+                window.end() - self.optimal_hold_ms
+            )?,
+            None => writeln!(f, ", record can't be beaten")?,
+        }
+        writeln!(
+            f,
+            "{:>10} {:>20} {:>20}",
+            "hold_ms", "distance_mm", "margin_mm"
+        )?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:>10} {:>20} {:>+20}{}",
+                row.hold_ms,
+                row.distance_mm,
+                row.margin_mm,
+                if row.hold_ms == self.optimal_hold_ms {
+                    " <- optimal"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        Ok(())
+    }
 }
 
 fn main() {
@@ -50,20 +330,97 @@ fn main() {
 
     let mut prod = 1;
     for (time, distance) in times.iter().zip(distances.iter()) {
-        prod *= ways(*time, *distance);
+        let winning = ways(*time, *distance);
+        println!("race of {time}ms, record {distance}mm: winning holds {winning:?}");
+        prod *= winning.map(|e| 1 + e.end() - e.start()).unwrap_or(0);
     }
     println!("{prod}");
 
     // pt 2
     let time = times
-        .into_iter()
+        .iter()
         .map(|e| e.to_string())
         .intersperse("".to_string())
-        .collect::<String>().parse::<u64>().unwrap();
+        .collect::<String>()
+        .parse::<u64>()
+        .unwrap();
     let distance = distances
-        .into_iter()
+        .iter()
         .map(|e| e.to_string())
         .intersperse("".to_string())
-        .collect::<String>().parse::<u64>().unwrap();
-    println!("{}", ways(time, distance));
+        .collect::<String>()
+        .parse::<u64>()
+        .unwrap();
+    let winning = ways(time, distance);
+    println!("race of {time}ms, record {distance}mm: winning holds {winning:?}");
+    println!("{}", winning.map(|e| 1 + e.end() - e.start()).unwrap_or(0));
+
+    // e.g. --charge-rate 2 --max-speed 30 --charge-limit 20 --handicap 3
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|e| e == name)
+            .map(|i| args[i + 1].parse::<u64>().unwrap())
+    };
+    let model = BoatModel {
+        charge: Charge::Linear(flag("--charge-rate").unwrap_or(1)),
+        max_speed: flag("--max-speed"),
+        charge_limit_ms: flag("--charge-limit"),
+        handicap_ms: flag("--handicap").unwrap_or(0),
+    };
+
+    let mut prod = 1;
+    for (time, distance) in times.iter().zip(distances.iter()) {
+        let holds = model.winning_holds(*time, *distance);
+        // races are short enough to check the closed form against every hold time
+        assert_eq!(holds, model.winning_holds_scan(*time, *distance));
+        println!("race of {time}ms, record {distance}mm: winning holds {holds:?}");
+        prod *= count_holds(&holds);
+    }
+    println!("{prod}");
+    let holds = model.winning_holds(time, distance);
+    println!("race of {time}ms, record {distance}mm: winning holds {holds:?}");
+    println!("{}", count_holds(&holds));
+
+    // a curve with no closed form, which falls back to scanning
+    let quadratic_model = BoatModel {
+        charge: Charge::Custom(|hold_ms| hold_ms * hold_ms / 4),
+        ..model
+    };
+    let mut prod = 1;
+    for (time, distance) in times.iter().zip(distances.iter()) {
+        prod *= count_holds(&quadratic_model.winning_holds(*time, *distance));
+    }
+    println!("with speed growing as hold^2 / 4: {prod}");
+
+    // --sweep prints a table per race, --sweep-csv <dir> writes them out.
+    // the part 2 race is tens of millions of ms long, so its table is sampled down to --sweep-rows rows
+    let max_rows = flag("--sweep-rows").unwrap_or(50);
+    let csv_dir = args
+        .iter()
+        .position(|e| e == "--sweep-csv")
+        .map(|i| args[i + 1].clone());
+    if args.iter().any(|e| e == "--sweep") || csv_dir.is_some() {
+        let mut races = times
+            .iter()
+            .zip(distances.iter())
+            .enumerate()
+            .map(|(i, (time, distance))| (format!("race_{}", i + 1), *time, *distance, None))
+            .collect::<Vec<_>>();
+        races.push(("race_part2".to_string(), time, distance, Some(max_rows)));
+
+        for (name, time, distance, max_rows) in races {
+            let sweep = RaceSweep::new(&model, time, distance, max_rows);
+            if args.iter().any(|e| e == "--sweep") {
+                println!("{sweep}");
+            }
+            if let Some(dir) = &csv_dir {
+                let path = format!("{dir}/{name}.csv");
+                sweep
+                    .write_csv(BufWriter::new(File::create(&path).unwrap()))
+                    .unwrap();
+                println!("wrote {path}");
+            }
+        }
+    }
 }
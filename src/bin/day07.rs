@@ -1,109 +1,57 @@
 use itertools::Itertools;
 use std::cmp::Ordering;
-use std::fmt::Debug;
+use std::collections::BTreeMap;
+use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 
 trait HasValue {
-    fn value(&self) -> i32;
+    fn value(&self, rules: &Rules) -> i32;
+    fn is_wild(&self, rules: &Rules) -> bool;
 }
 
 trait HasHandType {
-    fn score(&self) -> HandType;
-}
-
-#[derive(PartialEq, Eq, Default, Debug, Copy, Clone, Hash, PartialOrd, Ord)]
-enum Card {
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Jack,
-    Queen,
-    King,
-    #[default]
-    Ace,
-}
+    fn score(&self, rules: &Rules) -> HandType;
 
-impl HasValue for Card {
-    fn value(&self) -> i32 {
-        match self {
-            Card::Two => 2,
-            Card::Three => 3,
-            Card::Four => 4,
-            Card::Five => 5,
-            Card::Six => 6,
-            Card::Seven => 7,
-            Card::Eight => 8,
-            Card::Nine => 9,
-            Card::Ten => 10,
-            Card::Jack => 11,
-            Card::Queen => 12,
-            Card::King => 13,
-            Card::Ace => 14,
-        }
-    }
-}
-
-#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash, PartialOrd, Ord)]
-enum WildCard {
-    Jack,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Queen,
-    King,
-    Ace,
-}
-
-impl HasValue for WildCard {
-    fn value(&self) -> i32 {
-        match self {
-            WildCard::Jack => 1,
-            WildCard::Two => 2,
-            WildCard::Three => 3,
-            WildCard::Four => 4,
-            WildCard::Five => 5,
-            WildCard::Six => 6,
-            WildCard::Seven => 7,
-            WildCard::Eight => 8,
-            WildCard::Nine => 9,
-            WildCard::Ten => 10,
-            WildCard::Queen => 11,
-            WildCard::King => 12,
-            WildCard::Ace => 13,
+    // compared element by element when two hands have the same type
+    fn tie_break(&self, rules: &Rules) -> Vec<i32>;
+
+    // the hand type and tie break packed into one integer, so that sorting can compute it once per hand
+    // instead of rescoring both hands in every comparison.
+    // each tie break value gets 6 bits, most significant first, with the hand type above them all.
+    // None if the hand has too many cards or the deck too many ranks to fit
+    fn sort_key(&self, rules: &Rules) -> Option<u128> {
+        let tie_break = self.tie_break(rules);
+        if tie_break.len() > SORT_KEY_MAX_CARDS {
+            return None;
+        }
+        let mut key = self.score(rules) as u128;
+        for i in 0..SORT_KEY_MAX_CARDS {
+            let value = tie_break.get(i).copied().unwrap_or(0);
+            if !(0..1 << SORT_KEY_BITS_PER_CARD).contains(&value) {
+                return None;
+            }
+            key = (key << SORT_KEY_BITS_PER_CARD) | value as u128;
         }
+        Some(key)
     }
 }
 
-impl From<Card> for WildCard {
-    fn from(value: Card) -> Self {
-        match value {
-            Card::Two => WildCard::Two,
-            Card::Three => WildCard::Three,
-            Card::Four => WildCard::Four,
-            Card::Five => WildCard::Five,
-            Card::Six => WildCard::Six,
-            Card::Seven => WildCard::Seven,
-            Card::Eight => WildCard::Eight,
-            Card::Nine => WildCard::Nine,
-            Card::Ten => WildCard::Ten,
-            Card::Jack => WildCard::Jack,
-            Card::Queen => WildCard::Queen,
-            Card::King => WildCard::King,
-            Card::Ace => WildCard::Ace,
-        }
+const SORT_KEY_BITS_PER_CARD: usize = 6;
+// leaves the top 8 bits of a u128 for the hand type
+const SORT_KEY_MAX_CARDS: usize = 20;
+
+// a card is just its rank. what that rank is worth, and whether it's wild, is up to the Rules in play
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+struct Card(char);
+
+impl HasValue for Card {
+    fn value(&self, rules: &Rules) -> i32 {
+        rules.value(self.0)
+    }
+
+    fn is_wild(&self, rules: &Rules) -> bool {
+        rules.wild_ranks.contains(&self.0)
     }
 }
 
@@ -111,40 +59,108 @@ impl TryFrom<char> for Card {
     type Error = ();
     fn try_from(value: char) -> Result<Self, Self::Error> {
         match value {
-            'A' => Ok(Card::Ace),
-            '2' => Ok(Card::Two),
-            '3' => Ok(Card::Three),
-            '4' => Ok(Card::Four),
-            '5' => Ok(Card::Five),
-            '6' => Ok(Card::Six),
-            '7' => Ok(Card::Seven),
-            '8' => Ok(Card::Eight),
-            '9' => Ok(Card::Nine),
-            'T' => Ok(Card::Ten),
-            'J' => Ok(Card::Jack),
-            'Q' => Ok(Card::Queen),
-            'K' => Ok(Card::King),
-            // Some(v @ digit) => Err(()),
-            // v.to_digit() => {Err(())},
+            '2'..='9' | 'T' | 'J' | 'Q' | 'K' | 'A' => Ok(Card(value)),
             _ => Err(()),
         }
     }
 }
 
-impl TryFrom<&str> for Card {
-    type Error = ();
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let card_str = value.chars().nth(0).ok_or(())?;
-        let card = Card::try_from(card_str)?;
+// a card from a real deck, for poker rules. parsed from a rank followed by a suit, e.g. "Ts" or "Ah"
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+struct SuitedCard {
+    rank: Card,
+    suit: char,
+}
+
+impl HasValue for SuitedCard {
+    fn value(&self, rules: &Rules) -> i32 {
+        self.rank.value(rules)
+    }
 
-        Ok(card)
+    fn is_wild(&self, rules: &Rules) -> bool {
+        self.rank.is_wild(rules)
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Copy, Clone)]
-struct Hand<T>(pub [T; 5]);
+// defines a variant of the game: how ranks are ordered for tie breaks, and which ranks are wild
+#[derive(Clone, Debug)]
+struct Rules {
+    // every rank, weakest first
+    rank_order: Vec<char>,
+    wild_ranks: Vec<char>,
+    // real poker: straights and flushes count, and ties are broken by the grouped ranks instead of card order.
+    // only has an effect on suited hands
+    poker: bool,
+}
+
+impl Rules {
+    fn standard() -> Self {
+        Rules {
+            rank_order: "23456789TJQKA".chars().collect(),
+            wild_ranks: Vec::new(),
+            poker: false,
+        }
+    }
+
+    fn poker() -> Self {
+        Rules {
+            poker: true,
+            ..Rules::standard()
+        }
+    }
+
+    // part 2. jacks become jokers, which are wild but also the weakest card when breaking ties
+    fn jokers() -> Self {
+        Rules {
+            rank_order: "J23456789TQKA".chars().collect(),
+            wild_ranks: vec!['J'],
+            poker: false,
+        }
+    }
 
-#[derive(Debug, PartialEq, PartialOrd, Ord, Eq)]
+    // a custom deck. `alphabet` lists every rank weakest first, e.g. "123456789XYZ", and `wild` is any subset of it
+    fn with_alphabet(alphabet: &str, wild: &str) -> Result<Self, String> {
+        let rank_order = alphabet.chars().collect::<Vec<_>>();
+        if rank_order.is_empty() {
+            return Err("alphabet is empty".to_string());
+        }
+        if let Some(duplicate) = rank_order.iter().duplicates().next() {
+            return Err(format!(
+                "{duplicate:?} appears in the alphabet more than once"
+            ));
+        }
+        // rank values have to fit in a sort key
+        if rank_order.len() >= 1 << SORT_KEY_BITS_PER_CARD {
+            return Err(format!(
+                "alphabet has {} ranks, at most {} are supported",
+                rank_order.len(),
+                (1 << SORT_KEY_BITS_PER_CARD) - 1
+            ));
+        }
+        if let Some(rank) = wild.chars().find(|e| !rank_order.contains(e)) {
+            return Err(format!("wild rank {rank:?} is not in the alphabet"));
+        }
+        Ok(Rules {
+            rank_order,
+            wild_ranks: wild.chars().collect(),
+            poker: false,
+        })
+    }
+
+    fn value(&self, rank: char) -> i32 {
+        self.rank_order
+            .iter()
+            .position(|e| *e == rank)
+            .expect("rank is not part of these rules") as i32
+            + 1
+    }
+}
+
+// any number of cards
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Hand<T>(pub Vec<T>);
+
+#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Copy, Clone)]
 enum HandType {
     HighCard,
     OnePair,
@@ -152,194 +168,864 @@ enum HandType {
     ThreeOfAKind,
     // a straight is always uniquely identified by its start or end card. it's equivalent either way but we have to decide which it is
     // so in this case / program / context it is the high card.
+    // straights and flushes only exist under poker rules
+    Straight,
+    Flush,
     FullHouse,
     FourOfAKind,
+    StraightFlush,
     FiveOfAKind,
 }
 
-impl HasHandType for Hand<Card> {
-    fn score(&self) -> HandType {
-        let card_rank_bins = self.0.iter().counts_by(|e| *e);
-
-        let mut as_vec = card_rank_bins.iter().collect::<Vec<(_, _)>>();
-        // sort by rank value first
-        as_vec.sort_by_key(|e| e.0.value());
-        // then by count
-        // thus, ranks that occur with equal count will still be ordered properly so that the highest rank is at the end
-        as_vec.sort_by_key(|e| e.1);
-
-        let (highest_count, second_highest_count) = (as_vec.pop().unwrap(), as_vec.pop());
-        // println!("{:?} {:?}", highest_count, second_highest_count);
-
-        let current_match = match (
-            highest_count.1,
-            second_highest_count.map(|e| *e.1).unwrap_or(0),
+impl HandType {
+    // classifies a hand by how many times each rank occurs, biggest group first, e.g. [3, 2] is a full house.
+    // hands can be any size, so this is the best five card pattern the hand contains, like in seven card poker.
+    // three card hands top out at three of a kind, seven card hands can hold e.g. [4, 3] which is four of a kind
+    fn from_multiplicities(multiplicities: &[usize]) -> HandType {
+        match (
+            multiplicities.first().copied().unwrap_or(0),
+            multiplicities.get(1).copied().unwrap_or(0),
         ) {
-            (3, 2) => HandType::FullHouse,
-
-            (3, 1) => HandType::ThreeOfAKind,
+            (5.., _) => HandType::FiveOfAKind,
+            (4, _) => HandType::FourOfAKind,
+            (3, 2..) => HandType::FullHouse,
+            (3, _) => HandType::ThreeOfAKind,
             (2, 2) => HandType::TwoPair,
-            (4, 1) => HandType::FourOfAKind,
-            (5, 0) => HandType::FiveOfAKind,
-            (2, 1) => HandType::OnePair,
-            (1, 1) => HandType::HighCard,
-            _ => panic!("degenerate hand, this should not be possible."),
-        };
-
-        current_match
+            (2, _) => HandType::OnePair,
+            _ => HandType::HighCard,
+        }
     }
 }
 
-impl HasHandType for Hand<WildCard> {
-    fn score(&self) -> HandType {
+impl HasHandType for Hand<Card> {
+    fn score(&self, rules: &Rules) -> HandType {
         // theory: the best possible joker interpretation for the purposes of hand rank is the one that achieves the most duplicate cards.
         // thus, use jokers to extend already existing streaks if possible.
-        let card_rank_bins = self
+        let mut multiplicities = self
             .0
             .iter()
-            .filter(|e| **e != WildCard::Jack) // filter jokers out so that they don't stack with themselves
-            .counts_by(|e| *e);
-
-        let mut as_vec = card_rank_bins.iter().collect::<Vec<(_, _)>>();
-        // sort by rank value first
-        as_vec.sort_by_key(|e| e.0.value());
-        // then by count
-        // thus, ranks that occur with equal count will still be ordered properly so that the highest rank is at the end
-        as_vec.sort_by_key(|e| e.1);
-
-        let (highest_count, second_highest_count) = (as_vec.pop(), as_vec.pop());
-        // println!("{:?} {:?}", highest_count, second_highest_count);
-
-        let mut current_match = match (
-            highest_count.map(|e| *e.1).unwrap_or(0),
-            second_highest_count.map(|e| *e.1).unwrap_or(0),
-        ) {
-            (3, 2) => HandType::FullHouse,
-            (3, _) => HandType::ThreeOfAKind,
-            (2, 2) => HandType::TwoPair,
-            (4, _) => HandType::FourOfAKind,
-            (5, 0) => HandType::FiveOfAKind,
-            (2, _) => HandType::OnePair,
-            _ => HandType::HighCard,
+            .filter(|e| !e.is_wild(rules)) // filter jokers out so that they don't stack with themselves
+            .counts_by(|e| *e)
+            .into_values()
+            .collect::<Vec<_>>();
+        multiplicities.sort_by(|a, b| b.cmp(a));
+
+        // the jokers (if the rules have any) all join the biggest group
+        let num_jokers = self.0.iter().filter(|e| e.is_wild(rules)).count();
+        match multiplicities.first_mut() {
+            Some(biggest) => *biggest += num_jokers,
+            None => multiplicities.push(num_jokers),
+        }
+
+        HandType::from_multiplicities(&multiplicities)
+    }
+
+    fn tie_break(&self, rules: &Rules) -> Vec<i32> {
+        self.0.iter().map(|e| e.value(rules)).collect()
+    }
+}
+
+impl Hand<Card> {
+    // the rank every joker should pretend to be: the most common non wild rank, highest first on ties.
+    // if the whole hand is jokers, they all become the best non wild rank. None if there are no jokers
+    fn joker_substitute(&self, rules: &Rules) -> Option<Card> {
+        if !self.0.iter().any(|e| e.is_wild(rules)) {
+            return None;
+        }
+        let most_common = self
+            .0
+            .iter()
+            .filter(|e| !e.is_wild(rules))
+            .counts_by(|e| *e)
+            .into_iter()
+            .max_by_key(|(card, count)| (*count, card.value(rules)))
+            .map(|(card, _)| card);
+        most_common.or_else(|| {
+            rules
+                .rank_order
+                .iter()
+                .rev()
+                .find(|e| !rules.wild_ranks.contains(e))
+                .map(|e| Card(*e))
+        })
+    }
+
+    // the hand with every joker replaced by `card`
+    fn with_jokers_as(&self, rules: &Rules, card: Card) -> Hand<Card> {
+        Hand(
+            self.0
+                .iter()
+                .map(|e| if e.is_wild(rules) { card } else { *e })
+                .collect(),
+        )
+    }
+
+    // tries every possible assignment of non wild ranks to the jokers, each joker independently,
+    // and returns the best hand type any of them reaches
+    fn best_type_brute_force(&self, rules: &Rules) -> HandType {
+        let plain_rules = Rules {
+            wild_ranks: Vec::new(),
+            ..rules.clone()
         };
+        let joker_positions = self
+            .0
+            .iter()
+            .positions(|e| e.is_wild(rules))
+            .collect::<Vec<_>>();
+        let candidates = rules
+            .rank_order
+            .iter()
+            .filter(|e| !rules.wild_ranks.contains(e))
+            .map(|e| Card(*e))
+            .collect::<Vec<_>>();
+
+        joker_positions
+            .iter()
+            .map(|_| candidates.iter())
+            .multi_cartesian_product()
+            .map(|assignment| {
+                let mut hand = self.clone();
+                for (position, card) in joker_positions.iter().zip(assignment) {
+                    hand.0[*position] = *card;
+                }
+                hand.score(&plain_rules)
+            })
+            .max()
+            // no jokers at all, multi_cartesian_product of nothing yields nothing
+            .unwrap_or_else(|| self.score(&plain_rules))
+    }
+}
+
+impl Hand<SuitedCard> {
+    fn ranks(&self) -> Hand<Card> {
+        Hand(self.0.iter().map(|e| e.rank).collect())
+    }
+
+    // the value of the high card if every card is part of one straight.
+    // the wheel (A2345 for five cards) has the ace playing low, so it's a straight to the 5
+    fn straight_high(&self, rules: &Rules) -> Option<i32> {
+        let values = self
+            .0
+            .iter()
+            .map(|e| e.value(rules))
+            .sorted()
+            .collect::<Vec<_>>();
+        if values.len() < 2 {
+            return None;
+        }
+        if values.windows(2).all(|w| w[1] == w[0] + 1) {
+            return values.last().copied();
+        }
+        // lowest ranks plus the highest rank
+        let n = values.len();
+        let mut wheel = (1..n as i32).collect::<Vec<_>>();
+        wheel.push(rules.rank_order.len() as i32);
+        (values == wheel).then_some(n as i32 - 1)
+    }
 
-        // we've determined a preliminary hand type, use the jokers to extend/mutate it
-        let num_jokers = self.0.iter().filter(|e| **e == WildCard::Jack).count();
+    fn is_flush(&self) -> bool {
+        self.0.iter().all(|e| e.suit == self.0[0].suit)
+    }
+}
 
-        for _ in 0..num_jokers {
-            current_match = match current_match {
-                HandType::HighCard => HandType::OnePair,
-                HandType::OnePair => HandType::ThreeOfAKind,
-                HandType::TwoPair => HandType::FullHouse,
-                HandType::ThreeOfAKind => HandType::FourOfAKind,
-                HandType::FullHouse => HandType::FourOfAKind,
-                HandType::FourOfAKind => HandType::FiveOfAKind,
-                HandType::FiveOfAKind => HandType::FiveOfAKind,
-            };
+impl HasHandType for Hand<SuitedCard> {
+    fn score(&self, rules: &Rules) -> HandType {
+        let by_rank = self.ranks().score(rules);
+        if !rules.poker {
+            return by_rank;
+        }
+        match (self.straight_high(rules).is_some(), self.is_flush()) {
+            (true, true) => HandType::StraightFlush,
+            // a flush beats a straight, but both lose to a full house or better
+            (_, true) => by_rank.max(HandType::Flush),
+            (true, _) => by_rank.max(HandType::Straight),
+            _ => by_rank,
         }
+    }
 
-        current_match
+    fn tie_break(&self, rules: &Rules) -> Vec<i32> {
+        if !rules.poker {
+            return self.ranks().tie_break(rules);
+        }
+        if let Some(high) = self.straight_high(rules) {
+            return vec![high];
+        }
+        // bigger groups first, then higher ranks, e.g. a full house compares its triple before its pair
+        self.0
+            .iter()
+            .counts_by(|e| e.value(rules))
+            .into_iter()
+            .sorted_by_key(|(value, count)| (std::cmp::Reverse(*count), std::cmp::Reverse(*value)))
+            .map(|(value, _)| value)
+            .collect()
     }
 }
 
-impl<T> TryFrom<&str> for Hand<T>
-where
-    T: Default + TryFrom<char> + Copy,
-    (): From<<T as TryFrom<char>>::Error>,
-{
-    type Error = ();
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let mut hand_inner = [T::default(); 5];
-        let split = value.chars();
-        let five = split.take(5).collect::<Vec<_>>();
-        if five.len() != 5 {
-            return Err(());
+#[derive(Debug)]
+enum ParseError {
+    UnknownCard {
+        card: char,
+        column: usize,
+    },
+    UnknownSuit {
+        suit: char,
+        column: usize,
+    },
+    // the last rank of a suited hand has no suit after it
+    MissingSuit {
+        column: usize,
+    },
+    // a real deck only has one of each suited card
+    DuplicateCard {
+        card: String,
+        column: usize,
+        first_column: usize,
+    },
+    WrongHandSize {
+        expected: usize,
+        found: usize,
+        column: usize,
+    },
+    MissingBid {
+        column: usize,
+    },
+    InvalidBid {
+        bid: String,
+        column: usize,
+    },
+    UnexpectedInput {
+        input: String,
+        column: usize,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownCard { card, column } => {
+                write!(f, "column {column}: {card:?} is not a card in this deck")
+            }
+            ParseError::UnknownSuit { suit, column } => {
+                write!(
+                    f,
+                    "column {column}: {suit:?} is not a suit, expected s, h, d or c"
+                )
+            }
+            ParseError::MissingSuit { column } => {
+                write!(f, "column {column}: card has no suit")
+            }
+            ParseError::DuplicateCard {
+                card,
+                column,
+                first_column,
+            } => write!(
+                f,
+                "column {column}: {card} is already in this hand at column {first_column}"
+            ),
+            ParseError::WrongHandSize {
+                expected,
+                found,
+                column,
+            } => write!(
+                f,
+                "column {column}: expected a hand of {expected} cards, found {found}"
+            ),
+            ParseError::MissingBid { column } => write!(f, "column {column}: missing bid"),
+            ParseError::InvalidBid { bid, column } => {
+                write!(f, "column {column}: bid {bid:?} is not a whole number")
+            }
+            ParseError::UnexpectedInput { input, column } => {
+                write!(f, "column {column}: unexpected {input:?} after the bid")
+            }
         }
+    }
+}
 
-        hand_inner[0] = T::try_from(five[0])?;
-        hand_inner[1] = T::try_from(five[1])?;
-        hand_inner[2] = T::try_from(five[2])?;
-        hand_inner[3] = T::try_from(five[3])?;
-        hand_inner[4] = T::try_from(five[4])?;
+impl Hand<Card> {
+    // every character must be a rank in the rules' alphabet.
+    // `column` is where the hand starts in its line, for error positions
+    fn parse(value: &str, rules: &Rules, column: usize) -> Result<Self, ParseError> {
+        let hand_inner = value
+            .chars()
+            .enumerate()
+            .map(|(i, e)| {
+                if rules.rank_order.contains(&e) {
+                    Ok(Card(e))
+                } else {
+                    Err(ParseError::UnknownCard {
+                        card: e,
+                        column: column + i,
+                    })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Hand(hand_inner))
     }
 }
 
-impl<T> PartialOrd for Hand<T>
+// splits a line on whitespace, keeping the 1-based column each piece starts at
+fn tokens_with_columns(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (column, (i, c)) in (1..).zip(line.char_indices()) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column, i)),
+            (true, Some((token_column, token_start))) => {
+                tokens.push((token_column, &line[token_start..i]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some((token_column, token_start)) = start {
+        tokens.push((token_column, &line[token_start..]));
+    }
+    tokens
+}
+
+// parses a "<hand> <bid>" line. every hand in a game has to be the same size
+fn parse_hand_and_bid(
+    line: &str,
+    rules: &Rules,
+    hand_size: usize,
+) -> Result<(Hand<Card>, usize), ParseError> {
+    let tokens = tokens_with_columns(line);
+    let Some((hand_column, hand)) = tokens.first() else {
+        return Err(ParseError::MissingBid { column: 1 });
+    };
+    let hand = Hand::parse(hand, rules, *hand_column)?;
+    if hand.0.len() != hand_size {
+        return Err(ParseError::WrongHandSize {
+            expected: hand_size,
+            found: hand.0.len(),
+            column: *hand_column,
+        });
+    }
+
+    Ok((hand, parse_bid(line, &tokens)?))
+}
+
+// the bid after the hand, which has to be the last thing on the line
+fn parse_bid(line: &str, tokens: &[(usize, &str)]) -> Result<usize, ParseError> {
+    let Some((bid_column, bid)) = tokens.get(1) else {
+        return Err(ParseError::MissingBid {
+            column: line.chars().count() + 1,
+        });
+    };
+    let bid = bid.parse::<usize>().map_err(|_| ParseError::InvalidBid {
+        bid: bid.to_string(),
+        column: *bid_column,
+    })?;
+
+    if let Some((column, input)) = tokens.get(2) {
+        return Err(ParseError::UnexpectedInput {
+            input: input.to_string(),
+            column: *column,
+        });
+    }
+    Ok(bid)
+}
+
+// parses a hand of suited cards, written back to back like "AhKhQhJhTh".
+// `column` is where the hand starts in its line, for error positions
+fn parse_suited_hand(value: &str, column: usize) -> Result<Hand<SuitedCard>, ParseError> {
+    let chars = value.chars().collect::<Vec<_>>();
+    let hand_inner = chars
+        .chunks(2)
+        .enumerate()
+        .map(|(i, e)| {
+            let column = column + 2 * i;
+            let rank =
+                Card::try_from(e[0]).map_err(|_| ParseError::UnknownCard { card: e[0], column })?;
+            match e.get(1) {
+                Some(suit @ ('s' | 'h' | 'd' | 'c')) => Ok(SuitedCard { rank, suit: *suit }),
+                Some(suit) => Err(ParseError::UnknownSuit {
+                    suit: *suit,
+                    column: column + 1,
+                }),
+                None => Err(ParseError::MissingSuit { column }),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (i, card) in hand_inner.iter().enumerate() {
+        if let Some(first) = hand_inner[..i].iter().position(|e| e == card) {
+            return Err(ParseError::DuplicateCard {
+                card: format!("{}{}", card.rank.0, card.suit),
+                column: column + 2 * i,
+                first_column: column + 2 * first,
+            });
+        }
+    }
+
+    Ok(Hand(hand_inner))
+}
+
+// parses a "<suited hand> <bid>" line for --poker, which is always played with 5 cards
+fn parse_suited_hand_and_bid(line: &str) -> Result<(Hand<SuitedCard>, usize), ParseError> {
+    const POKER_HAND_SIZE: usize = 5;
+    let tokens = tokens_with_columns(line);
+    let Some((hand_column, hand)) = tokens.first() else {
+        return Err(ParseError::MissingBid { column: 1 });
+    };
+    let hand = parse_suited_hand(hand, *hand_column)?;
+    if hand.0.len() != POKER_HAND_SIZE {
+        return Err(ParseError::WrongHandSize {
+            expected: POKER_HAND_SIZE,
+            found: hand.0.len(),
+            column: *hand_column,
+        });
+    }
+    Ok((hand, parse_bid(line, &tokens)?))
+}
+
+impl<T> Hand<T>
 where
-    T: HasValue + Eq + PartialEq + PartialOrd,
-    T: Debug,
     Hand<T>: HasHandType,
 {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let left_score = self.score();
-        let right_score = other.score();
-        let Some(ord) = left_score.partial_cmp(&right_score) else {
-            return None;
-        };
-        match ord {
-            a @ Ordering::Less | a @ Ordering::Greater => Some(a),
-            Ordering::Equal => {
-                let mut current_order = Ordering::Equal;
-                for i in 0..5 {
-                    if current_order != Ordering::Equal {
-                        break;
-                    }
-                    current_order = self.0[i].partial_cmp(&other.0[i]).unwrap();
-                }
+    // orders by hand type first, then by the tie break values
+    fn cmp_with(&self, other: &Self, rules: &Rules) -> Ordering {
+        self.score(rules)
+            .cmp(&other.score(rules))
+            .then_with(|| self.tie_break(rules).cmp(&other.tie_break(rules)))
+    }
+}
 
-                Some(current_order)
-            }
-        }
+// weakest first. uses the packed sort key when every hand fits in one, otherwise the type and tie break as they are
+fn sort_hands<T>(hands_and_bids: &mut [(Hand<T>, usize)], rules: &Rules)
+where
+    Hand<T>: HasHandType,
+{
+    let fits = hands_and_bids
+        .iter()
+        .all(|e| e.0 .0.len() <= SORT_KEY_MAX_CARDS)
+        && rules.rank_order.len() < 1 << SORT_KEY_BITS_PER_CARD;
+    if fits {
+        hands_and_bids.sort_by_cached_key(|e| e.0.sort_key(rules).unwrap());
+    } else {
+        hands_and_bids.sort_by_cached_key(|e| (e.0.score(rules), e.0.tie_break(rules)));
     }
 }
 
-impl<T> Ord for Hand<T>
+fn total_winnings<T>(hands_and_bids: &mut [(Hand<T>, usize)], rules: &Rules) -> usize
 where
-    T: HasValue + Eq + PartialEq + PartialOrd,
-    T: Debug,
     Hand<T>: HasHandType,
 {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+    sort_hands(hands_and_bids, rules);
+
+    let mut winnings = 0;
+    for (rank, (_, bid)) in hands_and_bids.iter().enumerate() {
+        winnings += (1 + rank) * bid;
+    }
+    winnings
+}
+
+// one line of the explain report
+struct Explanation {
+    rank: usize,
+    hand: String,
+    // the type with jokers counted as plain cards
+    base_type: HandType,
+    jokered_type: HandType,
+    jokers_as: Option<Card>,
+    bid: usize,
+    winnings: usize,
+}
+
+// ranks the hands under `rules` and explains what the jokers did for each of them.
+// also checks score() against trying every possible joker substitution, and panics if it was beaten
+fn explain(hands_and_bids: &mut [(Hand<Card>, usize)], rules: &Rules) -> Vec<Explanation> {
+    let plain_rules = Rules {
+        wild_ranks: Vec::new(),
+        ..rules.clone()
+    };
+    sort_hands(hands_and_bids, rules);
+
+    let mut explanations = Vec::new();
+    for (i, (hand, bid)) in hands_and_bids.iter().enumerate() {
+        let jokered_type = hand.score(rules);
+        let jokers_as = hand.joker_substitute(rules);
+        assert_eq!(
+            jokered_type,
+            hand.best_type_brute_force(rules),
+            "{hand:?} could do better"
+        );
+        if let Some(card) = jokers_as {
+            assert_eq!(
+                hand.with_jokers_as(rules, card).score(&plain_rules),
+                jokered_type,
+                "{hand:?} with jokers as {card:?} doesn't reach {jokered_type:?}"
+            );
+        }
+        explanations.push(Explanation {
+            rank: i + 1,
+            hand: hand.0.iter().map(|e| e.0).collect(),
+            base_type: hand.score(&plain_rules),
+            jokered_type,
+            jokers_as,
+            bid: *bid,
+            winnings: (i + 1) * bid,
+        });
+    }
+    explanations
+}
+
+fn write_explanations_csv(explanations: &[Explanation], mut w: impl Write) -> std::io::Result<()> {
+    writeln!(w, "rank,hand,base_type,jokered_type,jokers_as,bid,winnings")?;
+    for e in explanations {
+        writeln!(
+            w,
+            "{},{},{:?},{:?},{},{},{}",
+            e.rank,
+            e.hand,
+            e.base_type,
+            e.jokered_type,
+            e.jokers_as.map(|e| e.0.to_string()).unwrap_or_default(),
+            e.bid,
+            e.winnings
+        )?;
+    }
+    Ok(())
+}
+
+// small seeded prng (xorshift64*), so that synthetic inputs are the same every run
+struct XorShift64(u64);
+
+impl XorShift64 {
+    fn new(seed: u64) -> Self {
+        // the state must never be zero
+        XorShift64(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+fn random_hands(
+    n: usize,
+    hand_size: usize,
+    rules: &Rules,
+    rng: &mut XorShift64,
+) -> Vec<Hand<Card>> {
+    (0..n)
+        .map(|_| {
+            Hand(
+                (0..hand_size)
+                    .map(|_| Card(rules.rank_order[rng.below(rules.rank_order.len())]))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+// every way to split n into parts no bigger than max_part, biggest part first
+fn partitions(n: usize, max_part: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut out = Vec::new();
+    for first in (1..=n.min(max_part)).rev() {
+        for mut rest in partitions(n - first, first) {
+            rest.insert(0, first);
+            out.push(rest);
+        }
+    }
+    out
+}
+
+fn binomial(n: u128, k: u128) -> u128 {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+// exact chance of each hand type when every card is drawn uniformly from the rules' ranks, like random_hands does.
+// counts the hands with j wild cards whose other cards form each multiplicity pattern, without enumerating them
+fn exact_type_probabilities(rules: &Rules, hand_size: usize) -> BTreeMap<HandType, f64> {
+    let num_wild = rules.wild_ranks.len() as u128;
+    let num_plain = rules.rank_order.len() as u128 - num_wild;
+    let total = (rules.rank_order.len() as u128).pow(hand_size as u32);
+    let factorial = |n: usize| (1..=n as u128).product::<u128>();
+
+    let mut counts = BTreeMap::new();
+    let max_jokers = if num_wild > 0 { hand_size } else { 0 };
+    for num_jokers in 0..=max_jokers {
+        // which positions hold the jokers, and which wild rank each one is
+        let joker_ways =
+            binomial(hand_size as u128, num_jokers as u128) * num_wild.pow(num_jokers as u32);
+        let plain_cards = hand_size - num_jokers;
+        for pattern in partitions(plain_cards, plain_cards) {
+            if pattern.len() as u128 > num_plain {
+                continue;
+            }
+            // pick a distinct rank for each group. groups of the same size are interchangeable
+            let rank_ways = (0..pattern.len() as u128).fold(1, |acc, i| acc * (num_plain - i))
+                / pattern
+                    .iter()
+                    .counts()
+                    .values()
+                    .map(|e| factorial(*e))
+                    .product::<u128>();
+            // then arrange the cards in the remaining positions
+            let arrangements =
+                factorial(plain_cards) / pattern.iter().map(|e| factorial(*e)).product::<u128>();
+
+            let mut jokered = pattern.clone();
+            match jokered.first_mut() {
+                Some(biggest) => *biggest += num_jokers,
+                None => jokered.push(num_jokers),
+            }
+            *counts
+                .entry(HandType::from_multiplicities(&jokered))
+                .or_insert(0u128) += joker_ways * rank_ways * arrangements;
+        }
+    }
+    assert_eq!(counts.values().sum::<u128>(), total);
+    counts
+        .into_iter()
+        .map(|(hand_type, count)| (hand_type, count as f64 / total as f64))
+        .collect()
+}
+
+// deals n random hands and compares how often each type comes up against the exact probabilities
+fn simulate(rules: &Rules, n: usize, hand_size: usize, seed: u64) {
+    let mut rng = XorShift64::new(seed);
+    let mut observed = BTreeMap::new();
+    for hand in random_hands(n, hand_size, rules, &mut rng) {
+        *observed.entry(hand.score(rules)).or_insert(0usize) += 1;
+    }
+    let exact = exact_type_probabilities(rules, hand_size);
+
+    println!(
+        "{n} hands of {hand_size}, wild ranks {:?}, seed {seed}",
+        rules.wild_ranks
+    );
+    println!(
+        "{:<14} {:>10} {:>10} {:>10} {:>8}",
+        "hand type", "count", "observed", "exact", "z"
+    );
+    for (hand_type, p) in exact {
+        let count = observed.get(&hand_type).copied().unwrap_or(0);
+        let frequency = count as f64 / n as f64;
+        // how many standard deviations the observed count is from the expected one
+        let z = (count as f64 - n as f64 * p) / (n as f64 * p * (1.0 - p)).sqrt();
+        println!(
+            "{:<14} {count:>10} {frequency:>10.6} {p:>10.6} {z:>+8.2}",
+            format!("{hand_type:?}")
+        );
+    }
+}
+
+// sorts the same synthetic hands by comparing them directly and by precomputed key, and times both
+fn bench_sorting(n: usize) {
+    let mut rng = XorShift64::new(7);
+    for rules in [Rules::standard(), Rules::jokers()] {
+        let hands = random_hands(n, 5, &rules, &mut rng);
+
+        let mut by_comparison = hands.clone();
+        let start = std::time::Instant::now();
+        by_comparison.sort_by(|a, b| a.cmp_with(b, &rules));
+        let comparison_time = start.elapsed();
+
+        let mut by_key = hands;
+        let start = std::time::Instant::now();
+        by_key.sort_by_cached_key(|e| e.sort_key(&rules).unwrap());
+        let key_time = start.elapsed();
+
+        // duplicate hands can land in either order, but every position must hold an equal hand
+        assert!(by_comparison
+            .iter()
+            .zip(by_key.iter())
+            .all(|(a, b)| a.cmp_with(b, &rules) == Ordering::Equal));
+        println!(
+            "{n} hands, wild ranks {:?}: comparing {comparison_time:?}, sort keys {key_time:?} ({:.1}x)",
+            rules.wild_ranks,
+            comparison_time.as_secs_f64() / key_time.as_secs_f64()
+        );
     }
 }
 
 fn main() {
-    let file = File::open("data/day07.txt").unwrap();
+    // --input <file> to play a different set of hands, which can be any size
+    let args = std::env::args().collect::<Vec<_>>();
+    let path = args
+        .iter()
+        .position(|e| e == "--input")
+        .map(|i| args[i + 1].as_str())
+        .unwrap_or("data/day07.txt");
+    let file = File::open(path).unwrap();
     let reader = BufReader::new(file);
     let mut lines = reader.lines();
 
+    let flag = |name: &str| {
+        args.iter()
+            .position(|e| e == name)
+            .map(|i| args[i + 1].as_str())
+    };
+    // --alphabet <ranks> [--wild <ranks>] plays with a custom deck instead of both puzzle parts,
+    // e.g. --alphabet 123456789XYZ --wild X
+    let custom_rules = match flag("--alphabet") {
+        Some(alphabet) => match Rules::with_alphabet(alphabet, flag("--wild").unwrap_or("")) {
+            Ok(rules) => Some(rules),
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        },
+        None => None,
+    };
+    let parse_rules = custom_rules.clone().unwrap_or_else(Rules::standard);
+    // hands have 5 cards unless --input-hand-size <n> says otherwise. with --input-hand-size auto, or a custom
+    // --alphabet game, every hand has to match the first one instead
+    let mut hand_size = match flag("--input-hand-size") {
+        Some("auto") => None,
+        Some(size) => match size.parse::<usize>() {
+            Ok(size) => Some(size),
+            Err(_) => {
+                println!("--input-hand-size {size:?} is not a number or auto");
+                return;
+            }
+        },
+        None if custom_rules.is_some() => None,
+        None => Some(5),
+    };
+
     let mut hands_and_bids = Vec::new();
+    let mut line_number = 0;
     while let Some(Ok(line)) = lines.next() {
-        let mut split = line.split_whitespace();
-        let hand: Hand<Card> = split.next().unwrap().try_into().unwrap();
-        let bid = split.next().unwrap().parse::<usize>().unwrap();
-
-        hands_and_bids.push((hand, bid));
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let expected_size = *hand_size.get_or_insert_with(|| {
+            line.split_whitespace()
+                .next()
+                .map(|e| e.chars().count())
+                .unwrap_or(0)
+        });
+        match parse_hand_and_bid(&line, &parse_rules, expected_size) {
+            Ok(hand_and_bid) => hands_and_bids.push(hand_and_bid),
+            Err(e) => {
+                println!("{path} line {line_number}, {e}");
+                return;
+            }
+        }
     }
 
-    hands_and_bids.sort_by_key(|e| e.0);
+    let joker_rules = match custom_rules {
+        Some(rules) => {
+            println!("{}", total_winnings(&mut hands_and_bids, &rules));
+            rules
+        }
+        None => {
+            println!(
+                "{}",
+                total_winnings(&mut hands_and_bids, &Rules::standard())
+            );
+
+            // same hands, but now jacks are jokers
+            println!("{}", total_winnings(&mut hands_and_bids, &Rules::jokers()));
+            Rules::jokers()
+        }
+    };
 
-    let mut winnings = 0;
-    for (rank, (_, bid)) in hands_and_bids.iter().enumerate() {
-        winnings += (1 + rank) * bid;
+    // --explain prints the joker report for every hand, --explain-csv <file> writes it out
+    let csv_path = args
+        .iter()
+        .position(|e| e == "--explain-csv")
+        .map(|i| args[i + 1].clone());
+    if args.iter().any(|e| e == "--explain") || csv_path.is_some() {
+        let explanations = explain(&mut hands_and_bids, &joker_rules);
+        if args.iter().any(|e| e == "--explain") {
+            println!(
+                "{:>5} {:<8} {:<14} {:<14} {:<9} {:>5} {:>9}",
+                "rank", "hand", "base type", "jokered type", "jokers as", "bid", "winnings"
+            );
+            for e in &explanations {
+                println!(
+                    "{:>5} {:<8} {:<14} {:<14} {:<9} {:>5} {:>9}",
+                    e.rank,
+                    e.hand,
+                    format!("{:?}", e.base_type),
+                    format!("{:?}", e.jokered_type),
+                    e.jokers_as.map(|e| e.0.to_string()).unwrap_or_default(),
+                    e.bid,
+                    e.winnings
+                );
+            }
+        }
+        if let Some(path) = csv_path {
+            write_explanations_csv(&explanations, BufWriter::new(File::create(&path).unwrap()))
+                .unwrap();
+            println!("wrote {path}");
+        }
+        println!(
+            "every joker hand matches the best brute force substitution, total winnings {}",
+            explanations.iter().map(|e| e.winnings).sum::<usize>()
+        );
     }
-    println!("{winnings}");
 
-    // remap old card type to new card type
-    let mut new_hands_and_bids = hands_and_bids
-        .into_iter()
-        .map(|(hand, bid)| (Hand(hand.0.map(|e| WildCard::from(e))), bid))
-        .collect::<Vec<_>>();
+    // --bench [n] times sorting n synthetic hands, with and without precomputed sort keys
+    if let Some(i) = args.iter().position(|e| e == "--bench") {
+        let n = args
+            .get(i + 1)
+            .and_then(|e| e.parse::<usize>().ok())
+            .unwrap_or(100_000);
+        bench_sorting(n);
+    }
 
-    // sort using new sorting method, with Ord and such implemented using generics but with Value and HandType being returned from Trait methods
-    new_hands_and_bids.sort_by_key(|e| e.0);
+    // --simulate [n] deals n random hands under both rule sets, --seed and --hand-size adjust it
+    if let Some(i) = args.iter().position(|e| e == "--simulate") {
+        let flag = |name: &str| {
+            args.iter()
+                .position(|e| e == name)
+                .map(|i| args[i + 1].parse::<u64>().unwrap())
+        };
+        let n = args
+            .get(i + 1)
+            .and_then(|e| e.parse::<usize>().ok())
+            .unwrap_or(1_000_000);
+        let hand_size = flag("--hand-size").unwrap_or(5) as usize;
+        for rules in [Rules::standard(), Rules::jokers()] {
+            simulate(&rules, n, hand_size, flag("--seed").unwrap_or(2023));
+        }
+    }
 
-    let mut winnings = 0;
-    for (rank, (_, bid)) in new_hands_and_bids.iter().enumerate() {
-        winnings += (1 + rank) * bid;
+    // --poker <file> plays real poker on a file of suited hands and bids, e.g. "AhKhQhJhTh 100"
+    if let Some(i) = args.iter().position(|e| e == "--poker") {
+        let poker_path = &args[i + 1];
+        let reader = BufReader::new(File::open(poker_path).unwrap());
+        let mut poker_hands_and_bids = Vec::new();
+        for (line_number, line) in (1..).zip(reader.lines().map_while(Result::ok)) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_suited_hand_and_bid(&line) {
+                Ok(hand_and_bid) => poker_hands_and_bids.push(hand_and_bid),
+                Err(e) => {
+                    println!("{poker_path} line {line_number}, {e}");
+                    return;
+                }
+            }
+        }
+
+        let rules = Rules::poker();
+        let winnings = total_winnings(&mut poker_hands_and_bids, &rules);
+        for (rank, (hand, bid)) in poker_hands_and_bids.iter().enumerate() {
+            let cards = hand
+                .0
+                .iter()
+                .map(|e| format!("{}{}", e.rank.0, e.suit))
+                .join("");
+            println!("{:>4} {cards} {:?} {bid}", rank + 1, hand.score(&rules));
+        }
+        println!("{winnings}");
     }
-    println!("{winnings}");
 }
@@ -1,88 +1,1005 @@
-use std::collections::HashMap;
+use itertools::Itertools;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufReader, Read};
 
-fn gcd(mut a:usize, mut b:usize) -> usize {
+fn gcd(mut a: usize, mut b: usize) -> usize {
     //best to use euclidean algorithm
-    if a < b {
-        std::mem::swap(&mut a, &mut b);
+    while b != 0 {
+        (a, b) = (b, a % b);
     }
-    let mut remainder = a % b;
-    while a % remainder != 0 && b % remainder != 0 {
-        std::mem::swap(&mut a, &mut b);
-        std::mem::swap(&mut b, &mut remainder);
-        remainder = a % b;
+    a
+}
+
+// returns (g, x, y) with a*x + b*y = g = gcd(a, b)
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return (a, 1, 0);
     }
-    remainder
+    let (g, x, y) = extended_gcd(b, a % b);
+    (g, y, x - (a / b) * y)
 }
 
-fn main() {
-    let file = File::open("data/day08.txt").unwrap();
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+// combines t = r1 (mod m1) and t = r2 (mod m2) into a single congruence t = r (mod lcm(m1, m2)).
+// the moduli don't need to be coprime. None if the two can never hold at once
+fn crt(r1: u128, m1: u128, r2: u128, m2: u128) -> Option<(u128, u128)> {
+    let (g, p, _) = extended_gcd(m1 as i128, m2 as i128);
+    let diff = r2 as i128 - r1 as i128;
+    if diff % g != 0 {
+        return None;
+    }
+    let lcm = m1 / g as u128 * m2;
+    // t = r1 + m1 * k, where m1 * k = diff (mod m2)
+    let k = (diff / g).rem_euclid(m2 as i128 / g) * p.rem_euclid(m2 as i128 / g) % (m2 as i128 / g);
+    let r = (r1 as i128 + m1 as i128 * k).rem_euclid(lcm as i128);
+    Some((r as u128, lcm))
+}
 
-    let mut network = HashMap::new();
+// picks out start or goal nodes by name
+#[derive(Debug)]
+enum NodePredicate {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Regex(Regex),
+}
 
-    let pattern = lines.next().unwrap().unwrap();
+impl NodePredicate {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NodePredicate::Exact(e) => name == e,
+            NodePredicate::Prefix(e) => name.starts_with(e.as_str()),
+            NodePredicate::Suffix(e) => name.ends_with(e.as_str()),
+            NodePredicate::Regex(e) => e.is_match(name),
+        }
+    }
+}
 
-    let mut pattern_inf_iter = pattern.chars().into_iter().cycle();
+// "exact:AAA", "prefix:A", "suffix:Z" or "regex:^[AB].A$". a bare name means exact
+impl TryFrom<&str> for NodePredicate {
+    type Error = String;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let Some((kind, pattern)) = value.split_once(':') else {
+            return Ok(NodePredicate::Exact(value.to_string()));
+        };
+        match kind {
+            "exact" => Ok(NodePredicate::Exact(pattern.to_string())),
+            "prefix" => Ok(NodePredicate::Prefix(pattern.to_string())),
+            "suffix" => Ok(NodePredicate::Suffix(pattern.to_string())),
+            "regex" => Regex::new(pattern)
+                .map(NodePredicate::Regex)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("unknown node predicate {kind:?}")),
+        }
+    }
+}
+
+// a `name = (left, right)` or `name = (a: first, b: second, ...)` line of the input. unlabelled edges are L and R
+#[derive(Debug)]
+struct NodeLine<'a> {
+    name: &'a str,
+    edges: Vec<(char, &'a str)>,
+    line_number: usize,
+}
+
+// the instruction line, every label with its 1 based column
+#[derive(Debug)]
+struct InstructionLine {
+    labels: Vec<(char, usize)>,
+    line_number: usize,
+}
 
-    while let Some(Ok(line)) = lines.next() {
-        if line.len() < 1 {
+#[derive(Debug)]
+enum NetworkIssue {
+    MissingInstructions,
+    InvalidInstruction {
+        character: char,
+        line_number: usize,
+        column: usize,
+    },
+    MalformedNode {
+        line_number: usize,
+    },
+    DuplicateLabel {
+        label: char,
+        line_number: usize,
+    },
+    DuplicateNode {
+        name: String,
+        line_number: usize,
+        first_line_number: usize,
+    },
+    DanglingReference {
+        name: String,
+        line_number: usize,
+    },
+    // the instructions use a label this node has no edge for
+    MissingEdge {
+        name: String,
+        label: char,
+        line_number: usize,
+    },
+    // no goal can be reached from this start even ignoring the instructions
+    UnreachableGoal {
+        start: String,
+    },
+}
+
+impl Display for NetworkIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkIssue::MissingInstructions => write!(f, "no instructions before the nodes"),
+            NetworkIssue::InvalidInstruction {
+                character,
+                line_number,
+                column,
+            } => write!(
+                f,
+                "line {line_number} column {column}: {character:?} is not the label of any edge"
+            ),
+            NetworkIssue::MalformedNode { line_number } => write!(
+                f,
+                "line {line_number}: expected `name = (left, right)` or `name = (a: first, b: second, ...)`"
+            ),
+            NetworkIssue::DuplicateLabel { label, line_number } => {
+                write!(f, "line {line_number}: more than one edge labelled {label:?}")
+            }
+            NetworkIssue::DuplicateNode {
+                name,
+                line_number,
+                first_line_number,
+            } => write!(
+                f,
+                "line {line_number}: {name} is already defined on line {first_line_number}"
+            ),
+            NetworkIssue::DanglingReference { name, line_number } => {
+                write!(f, "line {line_number}: {name} is never defined")
+            }
+            NetworkIssue::MissingEdge {
+                name,
+                label,
+                line_number,
+            } => write!(
+                f,
+                "line {line_number}: {name} has no edge labelled {label:?}, but the instructions use it"
+            ),
+            NetworkIssue::UnreachableGoal { start } => {
+                write!(f, "no goal can be reached from {start}")
+            }
+        }
+    }
+}
+
+// the first non blank line is the instructions, every other non blank line a node. names can be any run of word
+// characters and whitespace is free. issues are collected rather than stopping at the first one, instruction
+// labels are only checked against the nodes in validate_network
+fn parse_network(data: &str) -> (InstructionLine, Vec<NodeLine<'_>>, Vec<NetworkIssue>) {
+    let node_pattern = Regex::new(r"^\s*(\w+)\s*=\s*\((.*)\)\s*$").unwrap();
+    let edge_pattern = Regex::new(r"^\s*(?:(\S)\s*:)?\s*(\w+)\s*$").unwrap();
+    let mut lines = data
+        .lines()
+        .enumerate()
+        .map(|(i, e)| (i + 1, e))
+        .filter(|(_, e)| !e.trim().is_empty());
+    let mut issues = Vec::new();
+
+    let instructions = match lines.next() {
+        Some((line_number, line)) => InstructionLine {
+            labels: (1..)
+                .zip(line.chars())
+                .filter(|(_, e)| !e.is_whitespace())
+                .map(|(column, e)| (e, column))
+                .collect(),
+            line_number,
+        },
+        None => InstructionLine {
+            labels: Vec::new(),
+            line_number: 1,
+        },
+    };
+    if instructions.labels.is_empty() {
+        issues.push(NetworkIssue::MissingInstructions);
+    }
+
+    let mut nodes = Vec::new();
+    'lines: for (line_number, line) in lines {
+        let Some(captures) = node_pattern.captures(line) else {
+            issues.push(NetworkIssue::MalformedNode { line_number });
             continue;
+        };
+        let mut edges = Vec::new();
+        let mut positional_labels = ['L', 'R'].into_iter();
+        for edge in captures.get(2).unwrap().as_str().split(',') {
+            let Some(edge) = edge_pattern.captures(edge) else {
+                issues.push(NetworkIssue::MalformedNode { line_number });
+                continue 'lines;
+            };
+            let label = match edge.get(1) {
+                Some(label) => label.as_str().chars().next().unwrap(),
+                None => {
+                    let Some(label) = positional_labels.next() else {
+                        issues.push(NetworkIssue::MalformedNode { line_number });
+                        continue 'lines;
+                    };
+                    label
+                }
+            };
+            if edges.iter().any(|(e, _)| *e == label) {
+                issues.push(NetworkIssue::DuplicateLabel { label, line_number });
+                continue 'lines;
+            }
+            edges.push((label, edge.get(2).unwrap().as_str()));
         }
-        let mut split = line.split("=");
-        let Some(node) = split.next().map(|e| e.trim()) else {
-            break;
+        nodes.push(NodeLine {
+            name: captures.get(1).unwrap().as_str(),
+            edges,
+            line_number,
+        });
+    }
+
+    (instructions, nodes, issues)
+}
+
+fn validate_network(instructions: &InstructionLine, nodes: &[NodeLine]) -> Vec<NetworkIssue> {
+    let mut issues = Vec::new();
+    let mut defined_at = HashMap::new();
+    for node in nodes {
+        if let Some(first_line_number) = defined_at.insert(node.name, node.line_number) {
+            issues.push(NetworkIssue::DuplicateNode {
+                name: node.name.to_string(),
+                line_number: node.line_number,
+                first_line_number,
+            });
+            // keep blaming the first definition
+            defined_at.insert(node.name, first_line_number);
+        }
+    }
+    for node in nodes {
+        for reference in node.edges.iter().map(|(_, e)| *e).unique() {
+            if !defined_at.contains_key(reference) {
+                issues.push(NetworkIssue::DanglingReference {
+                    name: reference.to_string(),
+                    line_number: node.line_number,
+                });
+            }
+        }
+    }
+
+    let all_labels = nodes
+        .iter()
+        .flat_map(|e| e.edges.iter().map(|(label, _)| *label))
+        .collect::<HashSet<_>>();
+    for (character, column) in &instructions.labels {
+        if !all_labels.contains(character) {
+            issues.push(NetworkIssue::InvalidInstruction {
+                character: *character,
+                line_number: instructions.line_number,
+                column: *column,
+            });
+        }
+    }
+    let used_labels = instructions
+        .labels
+        .iter()
+        .map(|(e, _)| *e)
+        .filter(|e| all_labels.contains(e))
+        .unique()
+        .collect::<Vec<_>>();
+    for node in nodes {
+        for label in &used_labels {
+            if !node.edges.iter().any(|(e, _)| e == label) {
+                issues.push(NetworkIssue::MissingEdge {
+                    name: node.name.to_string(),
+                    label: *label,
+                    line_number: node.line_number,
+                });
+            }
+        }
+    }
+    issues
+}
+
+// starts that can't reach any goal following edges in any order, so there's no point walking them
+fn unreachable_goals(network: &Network) -> Vec<NetworkIssue> {
+    let mut issues = Vec::new();
+    for start in (0..network.len() as u32).filter(|e| network.is_start[*e as usize]) {
+        if reachable_goals(network, start).is_empty() {
+            issues.push(NetworkIssue::UnreachableGoal {
+                start: network.names[start as usize].clone(),
+            });
+        }
+    }
+    issues
+}
+
+// prints any unreachable goals for the current predicates, true if walking makes sense
+fn goals_reachable(network: &Network) -> bool {
+    let issues = unreachable_goals(network);
+    for issue in &issues {
+        println!("{issue}");
+    }
+    issues.is_empty()
+}
+
+// the desert network with node names interned to dense ids, so walking it is just indexing arrays.
+// names are only kept around for output
+struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    // every edge label in the order they first appear, instructions are indices into this
+    labels: Vec<char>,
+    // neighbour of every node for every label, NO_EDGE where a node doesn't have that label
+    edges: Vec<Vec<u32>>,
+    // which nodes the walkers start from and which they're trying to reach, see set_predicates
+    is_start: Vec<bool>,
+    is_end: Vec<bool>,
+}
+
+const NO_EDGE: u32 = u32::MAX;
+
+impl Network {
+    // expects nodes that passed validate_network, every reference has to be defined
+    fn new(nodes: &[NodeLine]) -> Self {
+        let mut names = Vec::new();
+        let mut ids = HashMap::new();
+        for node in nodes {
+            ids.entry(node.name.to_string()).or_insert_with(|| {
+                names.push(node.name.to_string());
+                names.len() as u32 - 1
+            });
+        }
+        let labels = nodes
+            .iter()
+            .flat_map(|e| e.edges.iter().map(|(label, _)| *label))
+            .unique()
+            .collect::<Vec<_>>();
+        let mut edges = vec![vec![NO_EDGE; labels.len()]; names.len()];
+        for node in nodes {
+            for (label, target) in &node.edges {
+                let label = labels.iter().position(|e| e == label).unwrap();
+                edges[ids[node.name] as usize][label] = ids[*target];
+            }
+        }
+        let mut network = Network {
+            is_start: vec![false; names.len()],
+            is_end: vec![false; names.len()],
+            names,
+            ids,
+            labels,
+            edges,
         };
-        let Some(connections) = split.next() else {
+        // part 2's rules by default
+        network.set_predicates(
+            &NodePredicate::Suffix("A".to_string()),
+            &NodePredicate::Suffix("Z".to_string()),
+        );
+        network
+    }
+
+    fn set_predicates(&mut self, start: &NodePredicate, end: &NodePredicate) {
+        self.is_start = self.names.iter().map(|e| start.matches(e)).collect();
+        self.is_end = self.names.iter().map(|e| end.matches(e)).collect();
+    }
+
+    // the instruction labels as indices into each node's edges
+    fn instruction_indices(&self, instructions: &InstructionLine) -> Vec<usize> {
+        instructions
+            .labels
+            .iter()
+            .map(|(label, _)| self.labels.iter().position(|e| e == label).unwrap())
+            .collect()
+    }
+
+    // (label index, neighbour) for every edge out of node
+    fn neighbours(&self, node: u32) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.edges[node as usize]
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, e)| *e != NO_EDGE)
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+// breadth first search ignoring the instructions. for every node reached, the (node, label index) it was first
+// reached from, start itself maps to itself
+fn breadth_first(network: &Network, start: u32) -> Vec<Option<(u32, usize)>> {
+    let mut came_from = vec![None; network.len()];
+    came_from[start as usize] = Some((start, 0));
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for (label, next) in network.neighbours(node) {
+            if came_from[next as usize].is_none() {
+                came_from[next as usize] = Some((node, label));
+                queue.push_back(next);
+            }
+        }
+    }
+    came_from
+}
+
+// every goal that can be reached from start in any number of steps, in order of distance
+fn reachable_goals(network: &Network, start: u32) -> Vec<u32> {
+    let came_from = breadth_first(network, start);
+    let mut goals = (0..network.len() as u32)
+        .filter(|e| network.is_end[*e as usize] && came_from[*e as usize].is_some())
+        .map(|e| (path_to(&came_from, e).len(), e))
+        .collect::<Vec<_>>();
+    goals.sort();
+    goals.into_iter().map(|(_, e)| e).collect()
+}
+
+// the (label index, node) steps from the search's start to node
+fn path_to(came_from: &[Option<(u32, usize)>], mut node: u32) -> Vec<(usize, u32)> {
+    let mut path = Vec::new();
+    while let Some((previous, label)) = came_from[node as usize] {
+        if previous == node {
             break;
-        };
-        let connections = connections.trim();
-        let mut split = connections.split(", ");
-        let left_connection = split.next().unwrap()[1..].to_owned();
-        let right_connection = split.next().unwrap()[..3].to_owned();
+        }
+        path.push((label, node));
+        node = previous;
+    }
+    path.reverse();
+    path
+}
 
-        network.insert(node.to_owned(), (left_connection, right_connection));
+// the fewest steps from start to any goal if you could pick every turn, as (label index, node) steps
+fn shortest_path_to_goal(network: &Network, start: u32) -> Option<Vec<(usize, u32)>> {
+    let came_from = breadth_first(network, start);
+    let nearest = reachable_goals(network, start).into_iter().next()?;
+    Some(path_to(&came_from, nearest))
+}
+
+// strongly connected components with tarjan's algorithm, in reverse topological order
+fn strongly_connected_components(network: &Network) -> Vec<Vec<u32>> {
+    struct Tarjan<'a> {
+        network: &'a Network,
+        next_index: usize,
+        index: Vec<usize>,
+        low_link: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<u32>,
+        components: Vec<Vec<u32>>,
     }
 
-    // println!("{:?}", network);
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: u32) {
+            let v = node as usize;
+            self.index[v] = self.next_index;
+            self.low_link[v] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(node);
+            self.on_stack[v] = true;
 
-    let mut current_node = "AAA";
-    let mut step_count = 0;
-    while current_node != "ZZZ" {
-        current_node = match pattern_inf_iter.next().unwrap() {
-            'L' => network[current_node].0.as_str(),
-            'R' => network[current_node].1.as_str(),
-            _ => panic!(),
-        };
-        step_count += 1;
+            for (_, next) in self.network.neighbours(node) {
+                let w = next as usize;
+                if self.index[w] == usize::MAX {
+                    self.visit(next);
+                    self.low_link[v] = self.low_link[v].min(self.low_link[w]);
+                } else if self.on_stack[w] {
+                    self.low_link[v] = self.low_link[v].min(self.index[w]);
+                }
+            }
+
+            if self.low_link[v] == self.index[v] {
+                let mut component = Vec::new();
+                loop {
+                    let w = self.stack.pop().unwrap();
+                    self.on_stack[w as usize] = false;
+                    component.push(w);
+                    if w == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
     }
-    println!("took {step_count} steps to reach ZZZ");
 
-    let starting_nodes = network
-        .keys()
-        .filter(|e| e.chars().nth(2).unwrap() == 'A')
-        .map(|e| e.as_str())
+    let mut tarjan = Tarjan {
+        network,
+        next_index: 0,
+        index: vec![usize::MAX; network.len()],
+        low_link: vec![0; network.len()],
+        on_stack: vec![false; network.len()],
+        stack: Vec::new(),
+        components: Vec::new(),
+    };
+    for node in 0..network.len() as u32 {
+        if tarjan.index[node as usize] == usize::MAX {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components
+}
+
+// the instruction free view of the network: where each start could get to and how it hangs together
+fn print_reachability(network: &Network) {
+    for start in (0..network.len() as u32).filter(|e| network.is_start[*e as usize]) {
+        let name = &network.names[start as usize];
+        match shortest_path_to_goal(network, start) {
+            Some(path) => println!(
+                "{name}: nearest goal {} in {} steps via {}, path {}",
+                path.last()
+                    .map_or(name, |(_, e)| &network.names[*e as usize]),
+                path.len(),
+                path.iter()
+                    .map(|(e, _)| network.labels[*e])
+                    .collect::<String>(),
+                std::iter::once(name)
+                    .chain(path.iter().map(|(_, e)| &network.names[*e as usize]))
+                    .join(" -> ")
+            ),
+            None => println!("{name}: no goal reachable"),
+        }
+        let goals = reachable_goals(network, start);
+        if !goals.is_empty() {
+            println!(
+                "{name}: can reach goals {}",
+                goals.iter().map(|e| &network.names[*e as usize]).join(", ")
+            );
+        }
+    }
+
+    let components = strongly_connected_components(network);
+    // a single node is only a component worth mentioning if it loops back on itself
+    let nontrivial = components
+        .iter()
+        .filter(|e| e.len() > 1 || network.neighbours(e[0]).any(|(_, next)| next == e[0]))
+        .sorted_by_key(|e| std::cmp::Reverse(e.len()))
         .collect::<Vec<_>>();
+    println!(
+        "{} strongly connected components, {} of them cyclic",
+        components.len(),
+        nontrivial.len()
+    );
+    for component in nontrivial {
+        let names = |filter: &[bool]| {
+            component
+                .iter()
+                .filter(|e| filter[**e as usize])
+                .map(|e| &network.names[*e as usize])
+                .join(", ")
+        };
+        print!(
+            "  {} nodes, starts [{}], goals [{}]",
+            component.len(),
+            names(&network.is_start),
+            names(&network.is_end)
+        );
+        if component.len() <= 10 {
+            print!(": {}", names(&vec![true; network.len()]));
+        }
+        println!();
+    }
+}
+
+// how a single ghost moves forever. the state is (node, position in the instructions), and since there are
+// finitely many states the walk always ends up in a loop: `tail_length` steps of lead in, then the same
+// `cycle_length` states over and over
+#[derive(Debug)]
+struct WalkerCycle {
+    start: u32,
+    tail_length: usize,
+    cycle_length: usize,
+    // steps before the loop starts where the walker is on a Z node
+    tail_z_steps: Vec<usize>,
+    // steps in the first pass through the loop where the walker is on a Z node, these repeat every cycle_length
+    cycle_z_steps: Vec<usize>,
+}
 
-    let mut cycle_lengths = Vec::new();
-    for starting_node in starting_nodes {
-        let mut pattern_inf_iter = pattern.chars().into_iter().cycle();
-        let mut current_node = starting_node;
+impl WalkerCycle {
+    fn find(start: u32, instructions: &[usize], network: &Network) -> Self {
+        // first step each (node, instruction) state was seen at, indexed node * instructions.len() + instruction
+        let mut first_seen = vec![usize::MAX; network.len() * instructions.len()];
+        let mut z_steps = Vec::new();
+        let mut current_node = start;
         let mut step_count = 0usize;
-        while current_node.chars().nth(2).unwrap() != 'Z' {
-            current_node = match pattern_inf_iter.next().unwrap() {
-                'L' => network[current_node].0.as_str(),
-                'R' => network[current_node].1.as_str(),
-                _ => panic!(),
-            };
+        loop {
+            let instruction = step_count % instructions.len();
+            let state = current_node as usize * instructions.len() + instruction;
+            if first_seen[state] != usize::MAX {
+                let tail_length = first_seen[state];
+                let (tail_z_steps, cycle_z_steps) = z_steps.iter().partition(|e| **e < tail_length);
+                return WalkerCycle {
+                    start,
+                    tail_length,
+                    cycle_length: step_count - tail_length,
+                    tail_z_steps,
+                    cycle_z_steps,
+                };
+            }
+            first_seen[state] = step_count;
+            if network.is_end[current_node as usize] {
+                z_steps.push(step_count);
+            }
+
+            current_node = network.edges[current_node as usize][instructions[instruction]];
             step_count += 1;
         }
-        cycle_lengths.push(step_count);
     }
 
-    // compute lcm of numbers
-    let lcm: usize = cycle_lengths.into_iter().reduce(|a,b| a*b/gcd(a,b)).unwrap();
-    println!("{lcm}");
+    // steps until the walker first reaches a goal, if it ever does
+    fn first_z_step(&self) -> Option<usize> {
+        self.tail_z_steps
+            .first()
+            .or(self.cycle_z_steps.first())
+            .copied()
+    }
+
+    // the nodes visited in one pass through the loop, in order. may repeat a node at different instructions
+    fn cycle_nodes(&self, instructions: &[usize], network: &Network) -> Vec<u32> {
+        let mut current_node = self.start;
+        let mut nodes = Vec::new();
+        for step in 0..self.tail_length + self.cycle_length {
+            if step >= self.tail_length {
+                nodes.push(current_node);
+            }
+            current_node =
+                network.edges[current_node as usize][instructions[step % instructions.len()]];
+        }
+        nodes
+    }
+
+    fn is_on_z(&self, step: u128) -> bool {
+        if step < self.tail_length as u128 {
+            return self.tail_z_steps.contains(&(step as usize));
+        }
+        let in_cycle = (step - self.tail_length as u128) % self.cycle_length as u128;
+        self.cycle_z_steps
+            .contains(&(self.tail_length + in_cycle as usize))
+    }
+}
+
+// walks from every start node, printing how long each takes to first reach a goal and how it loops after that
+fn analyse_walkers(network: &Network, instructions: &[usize]) -> Vec<WalkerCycle> {
+    let mut walkers = Vec::new();
+    for starting_node in (0..network.len() as u32).filter(|e| network.is_start[*e as usize]) {
+        let walker = WalkerCycle::find(starting_node, instructions, network);
+        println!(
+            "{}: {}, tail {}, cycle {}, goals at {:?} then every {} steps from {:?}",
+            network.names[starting_node as usize],
+            walker
+                .first_z_step()
+                .map(|e| format!("first goal after {e} steps"))
+                .unwrap_or("never reaches a goal".to_string()),
+            walker.tail_length,
+            walker.cycle_length,
+            walker.tail_z_steps,
+            walker.cycle_length,
+            walker.cycle_z_steps
+        );
+        walkers.push(walker);
+    }
+    walkers
+}
+
+// the first step at which every walker is on a Z node at the same time, if there is one
+fn first_common_z_step(walkers: &[WalkerCycle]) -> Option<u128> {
+    let longest_tail = walkers.iter().map(|e| e.tail_length).max()?;
+
+    // before every walker is in its loop, the one with the longest lead in can only be on Z at one of its tail steps
+    let tail_walker = walkers.iter().find(|e| e.tail_length == longest_tail)?;
+    if let Some(step) = tail_walker
+        .tail_z_steps
+        .iter()
+        .map(|e| *e as u128)
+        .find(|step| walkers.iter().all(|e| e.is_on_z(*step)))
+    {
+        return Some(step);
+    }
+
+    // after that every walker is periodic. try each combination of Z offsets, one per walker, and solve
+    // the congruences with CRT. the answer is the earliest solution that's past every lead in
+    let mut best = None;
+    for offsets in walkers
+        .iter()
+        .map(|e| e.cycle_z_steps.iter())
+        .multi_cartesian_product()
+    {
+        let mut combined = Some((0u128, 1u128));
+        for (walker, offset) in walkers.iter().zip(offsets) {
+            combined = combined.and_then(|(r, m)| {
+                crt(
+                    r,
+                    m,
+                    (*offset % walker.cycle_length) as u128,
+                    walker.cycle_length as u128,
+                )
+            });
+        }
+        let Some((r, m)) = combined else {
+            continue;
+        };
+        let longest_tail = longest_tail as u128;
+        let step = if r >= longest_tail {
+            r
+        } else {
+            r + (longest_tail - r).div_ceil(m) * m
+        };
+        best = Some(best.map_or(step, |e: u128| e.min(step)));
+    }
+    best
+}
+
+// compute lcm of numbers. only right if each walker reaches Z exactly at multiples of its first Z step.
+// None if there are no walkers or any of them never reaches a goal
+fn lcm_of_first_goals(walkers: &[WalkerCycle]) -> Option<usize> {
+    walkers
+        .iter()
+        .map(|e| e.first_z_step())
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .reduce(|a, b| a * b / gcd(a, b))
+}
+
+// a walker whose goal status in the simulation doesn't match what its cycle predicts
+#[derive(Debug)]
+struct Divergence {
+    step: u64,
+    walker: String,
+    on_goal: bool,
+}
+
+// moves every walker one instruction at a time until they're all on a goal, checking each step against the cycle
+// analysis. Ok(None) if the budget runs out first
+fn simulate_lockstep(
+    network: &Network,
+    instructions: &[usize],
+    walkers: &[WalkerCycle],
+    budget: u64,
+    progress_every: u64,
+) -> Result<Option<u64>, Divergence> {
+    let mut positions = walkers.iter().map(|e| e.start).collect::<Vec<_>>();
+    for step in 0..=budget {
+        let mut all_on_goal = true;
+        for (walker, position) in walkers.iter().zip(&positions) {
+            let on_goal = network.is_end[*position as usize];
+            if on_goal != walker.is_on_z(step as u128) {
+                return Err(Divergence {
+                    step,
+                    walker: network.names[walker.start as usize].clone(),
+                    on_goal,
+                });
+            }
+            all_on_goal &= on_goal;
+        }
+        if all_on_goal {
+            return Ok(Some(step));
+        }
+        if progress_every != 0 && step != 0 && step % progress_every == 0 {
+            println!("step {step} of {budget}");
+        }
+
+        let instruction = instructions[step as usize % instructions.len()];
+        for position in positions.iter_mut() {
+            *position = network.edges[*position as usize][instruction];
+        }
+    }
+    Ok(None)
+}
+
+// the network as a graphviz digraph with edges labelled by the instructions that take them. start nodes are
+// boxes, goals double circles, and nodes that are both blue triple circles. every walker's cycle gets a fill
+// colour, nodes on several cycles are wedged
+fn network_dot(network: &Network, cycles: &[Vec<u32>]) -> String {
+    const PALETTE: [&str; 8] = [
+        "lightblue",
+        "lightsalmon",
+        "palegreen",
+        "plum",
+        "khaki",
+        "lightpink",
+        "aquamarine",
+        "wheat",
+    ];
+
+    let mut colours = vec![Vec::new(); network.len()];
+    for (i, cycle) in cycles.iter().enumerate() {
+        for node in cycle.iter().unique() {
+            colours[*node as usize].push(PALETTE[i % PALETTE.len()]);
+        }
+    }
+
+    let mut dot = String::from("digraph network {\n");
+    for (id, name) in network.names.iter().enumerate() {
+        let mut attributes = Vec::new();
+        match (network.is_start[id], network.is_end[id]) {
+            (true, false) => attributes.push("shape=box".to_string()),
+            (false, true) => attributes.push("shape=doublecircle".to_string()),
+            // graphviz only keeps one shape, so a start that's also a goal is a goal with a third, blue ring
+            (true, true) => {
+                attributes.push("shape=doublecircle peripheries=3 color=blue".to_string())
+            }
+            (false, false) => {}
+        }
+        if network.is_start[id] || network.is_end[id] {
+            attributes.push("penwidth=2".to_string());
+        }
+        match colours[id].len() {
+            0 => {}
+            1 => attributes.push(format!("style=filled fillcolor={}", colours[id][0])),
+            _ => attributes.push(format!(
+                "style=wedged fillcolor=\"{}\"",
+                colours[id].iter().join(":")
+            )),
+        }
+        dot.push_str(&format!("    {name:?} [{}];\n", attributes.join(" ")));
+    }
+    for (id, name) in network.names.iter().enumerate() {
+        // one arrow per neighbour, labelled with every label that leads there
+        let neighbours = network.neighbours(id as u32).into_group_map_by(|(_, e)| *e);
+        for (target, labels) in neighbours.into_iter().sorted_by_key(|(_, e)| e[0].0) {
+            let label = labels
+                .iter()
+                .map(|(e, _)| network.labels[*e])
+                .collect::<String>();
+            dot.push_str(&format!(
+                "    {name:?} -> {:?} [label={label:?}];\n",
+                network.names[target as usize]
+            ));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn main() {
+    let file = File::open("data/day08.txt").unwrap();
+    let mut reader = BufReader::new(file);
+    let mut data = String::new();
+    let _ = reader
+        .read_to_string(&mut data)
+        .expect("failed to read data into string");
+
+    let (instruction_line, nodes, mut issues) = parse_network(&data);
+    issues.extend(validate_network(&instruction_line, &nodes));
+    for issue in &issues {
+        println!("{issue}");
+    }
+    if !issues.is_empty() {
+        println!("network is broken, refusing to walk it");
+        return;
+    }
+
+    let mut network = Network::new(&nodes);
+    let instructions = network.instruction_indices(&instruction_line);
+
+    // the part 2 sample has no AAA
+    if network.ids.contains_key("AAA") {
+        network.set_predicates(
+            &NodePredicate::Exact("AAA".to_string()),
+            &NodePredicate::Exact("ZZZ".to_string()),
+        );
+        if goals_reachable(&network) {
+            match analyse_walkers(&network, &instructions)[0].first_z_step() {
+                Some(steps) => println!("took {steps} steps to reach ZZZ"),
+                None => println!("the instructions never lead to ZZZ"),
+            }
+        }
+    }
+
+    network.set_predicates(
+        &NodePredicate::Suffix("A".to_string()),
+        &NodePredicate::Suffix("Z".to_string()),
+    );
+    if goals_reachable(&network) {
+        let walkers = analyse_walkers(&network, &instructions);
+
+        match lcm_of_first_goals(&walkers) {
+            Some(lcm) => println!("lcm of first Z steps: {lcm}"),
+            None => println!("no lcm of first Z steps, not every walker reaches Z"),
+        }
+
+        match first_common_z_step(&walkers) {
+            Some(step) => println!("{step}"),
+            None => println!("the walkers are never all on Z at the same time"),
+        }
+    }
+
+    // --start <predicate> and --end <predicate> pick other start and goal nodes, e.g. --start regex:^[AB].A$ --end exact:ZZZ
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|e| e == name)
+            .map(|i| NodePredicate::try_from(args[i + 1].as_str()))
+    };
+    if let (Some(start), Some(end)) = (flag("--start"), flag("--end")) {
+        let (start, end) = match (start, end) {
+            (Ok(start), Ok(end)) => (start, end),
+            (Err(e), _) | (_, Err(e)) => {
+                println!("{e}");
+                return;
+            }
+        };
+        println!("starting from {start:?}, looking for {end:?}");
+        network.set_predicates(&start, &end);
+        if !goals_reachable(&network) {
+            return;
+        }
+        let walkers = analyse_walkers(&network, &instructions);
+        match first_common_z_step(&walkers) {
+            Some(step) => println!("all walkers are on a goal after {step} steps"),
+            None => println!("the walkers are never all on a goal at the same time"),
+        }
+    }
+
+    // --simulate [budget] walks every walker in lockstep for up to budget steps to check the cycle analysis,
+    // printing progress every --progress n steps
+    if let Some(i) = args.iter().position(|e| e == "--simulate") {
+        let budget = args
+            .get(i + 1)
+            .and_then(|e| e.parse().ok())
+            .unwrap_or(10_000_000u64);
+        let progress_every = args
+            .iter()
+            .position(|e| e == "--progress")
+            .map(|i| args[i + 1].parse().unwrap())
+            .unwrap_or(budget / 10);
+        let walkers = (0..network.len() as u32)
+            .filter(|e| network.is_start[*e as usize])
+            .map(|e| WalkerCycle::find(e, &instructions, &network))
+            .collect::<Vec<_>>();
+        let predicted = first_common_z_step(&walkers);
+        let lcm = lcm_of_first_goals(&walkers);
+
+        match simulate_lockstep(&network, &instructions, &walkers, budget, progress_every) {
+            Ok(Some(step)) if predicted == Some(step as u128) => {
+                println!("simulation agrees, all walkers are on a goal after {step} steps");
+                if lcm != Some(step as usize) {
+                    println!("but the lcm of first goals ({lcm:?}) doesn't");
+                }
+            }
+            Ok(Some(step)) => println!(
+                "step {step}: all walkers are on a goal, but the prediction was {predicted:?}"
+            ),
+            Ok(None) => match predicted {
+                Some(predicted) if predicted <= budget as u128 => println!(
+                    "step {predicted}: predicted every walker on a goal, the simulation didn't get there in {budget} steps"
+                ),
+                Some(predicted) => println!(
+                    "no disagreement in {budget} steps, too few to reach the prediction of {predicted}"
+                ),
+                None => println!("no disagreement in {budget} steps, and the walkers never line up"),
+            },
+            Err(divergence) => println!(
+                "step {}: {} is {} a goal, the prediction says otherwise",
+                divergence.step,
+                divergence.walker,
+                if divergence.on_goal { "on" } else { "off" }
+            ),
+        }
+    }
+
+    // --dot [path] writes the network with the current start and goal nodes, --dot-cycles also colours each walker's loop
+    if let Some(i) = args.iter().position(|e| e == "--dot") {
+        let path = args
+            .get(i + 1)
+            .filter(|e| !e.starts_with("--"))
+            .map(|e| e.as_str())
+            .unwrap_or("day08.dot");
+        let cycles = if args.iter().any(|e| e == "--dot-cycles") {
+            (0..network.len() as u32)
+                .filter(|e| network.is_start[*e as usize])
+                .map(|e| {
+                    WalkerCycle::find(e, &instructions, &network)
+                        .cycle_nodes(&instructions, &network)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        std::fs::write(path, network_dot(&network, &cycles)).unwrap();
+        println!("wrote network to {path}");
+    }
+
+    // --reachability shows shortest paths, reachable goals and strongly connected components ignoring the instructions
+    if args.iter().any(|e| e == "--reachability") {
+        print_reachability(&network);
+    }
 }
@@ -1,5 +1,7 @@
-fn predict_next_term(seq: &Vec<i32>) -> i32 {
-    let mut last_differences = seq.clone();
+use std::fmt::Display;
+
+fn predict_next_term(seq: &[i32]) -> i32 {
+    let mut last_differences = seq.to_vec();
     let mut differences = Vec::with_capacity(seq.len()); // match capacity of seq
 
     // store offsets of each level, with the 0th offset being the first term in the sequence
@@ -50,57 +52,372 @@ fn predict_next_term(seq: &Vec<i32>) -> i32 {
     *differences.last().unwrap()
 }
 
-// fn predict_previous_term(seq: &Vec<i32>) -> i32 {
-//     let mut last_differences = seq.clone();
-//     let mut differences = Vec::with_capacity(seq.len()); // match capacity of seq
-
-//     // store offsets of each level, with the 0th offset being the first term in the sequence
-//     let mut offsets = Vec::new();
-//     let mut level = 0;
-//     //ascend levels, computing differences
-
-//     loop {
-//         differences.clear();
-//         offsets.push(*last_differences.first().unwrap());
-//         for w in last_differences.windows(2) {
-//             differences.push(w[1] - w[0]);
-//         }
-
-//         level += 1;
-
-//         if differences.iter().all(|e| *e == 0) {
-//             std::mem::swap(&mut last_differences, &mut differences);
-//             break;
-//         }
-//         std::mem::swap(&mut last_differences, &mut differences);
-//     }
-
-//     // append 0
-//     last_differences.push(0);
-//     differences.push(*differences.last().unwrap());
-//     level -= 1;
-
-//     std::mem::swap(&mut last_differences, &mut differences);
-
-//     // and reconstruct
-//     loop {
-//         last_differences.clear();
-//         let mut v = offsets[level];
-//         last_differences.push(v);
-
-//         for delta in &differences {
-//             v += delta;
-//             last_differences.push(v);
-//         }
-//         std::mem::swap(&mut last_differences, &mut differences);
-//         if level > 0 {
-//             level -= 1;
-//         } else {
-//             break;
-//         }
-//     }
-//     *differences.last().unwrap()
-// }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
+// the first entry of each level of the difference table, stopping at the first level that's all zero. the
+// sequence is then a_n = sum over k of C(n, k) * differences[k], newton's forward difference form
+fn forward_differences(seq: &[i64]) -> Vec<i64> {
+    let mut level = seq.to_vec();
+    let mut differences = Vec::new();
+    while level.iter().any(|e| *e != 0) {
+        differences.push(level[0]);
+        level = level.windows(2).map(|w| w[1] - w[0]).collect();
+    }
+    differences
+}
+
+// the next `count` terms after the sequence going forward, or the `count` terms before it going backward,
+// nearest first
+fn extrapolate(seq: &[i64], count: usize, direction: Direction) -> Vec<i64> {
+    // the edge of the difference table on the side we're extending, top level first
+    let mut edge = Vec::new();
+    let mut level = seq.to_vec();
+    while level.iter().any(|e| *e != 0) {
+        edge.push(match direction {
+            Direction::Forward => *level.last().unwrap(),
+            Direction::Backward => level[0],
+        });
+        level = level.windows(2).map(|w| w[1] - w[0]).collect();
+    }
+
+    let mut terms = Vec::with_capacity(count);
+    for _ in 0..count {
+        // the bottom level is constant, every level above moves by the (already moved) level below
+        for i in (0..edge.len().saturating_sub(1)).rev() {
+            match direction {
+                Direction::Forward => edge[i] += edge[i + 1],
+                Direction::Backward => edge[i] -= edge[i + 1],
+            }
+        }
+        terms.push(edge.first().copied().unwrap_or(0));
+    }
+    terms
+}
+
+// the term at index n, where the sequence starts at 0. n can be far past the end or before the start
+fn evaluate_at(seq: &[i64], n: i64) -> BigInt {
+    let mut total = BigInt::from(0);
+    // C(n, k), built up with C(n, k + 1) = C(n, k) * (n - k) / (k + 1)
+    let mut binomial = BigInt::from(1);
+    for (k, difference) in forward_differences(seq).into_iter().enumerate() {
+        total = total.add(&binomial.mul_small(difference));
+        binomial = binomial
+            .mul_small(n - k as i64)
+            .div_exact_small(k as u64 + 1);
+    }
+    total
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// the polynomial through the sequence, with n = 0 at the first term. coefficient j (of n^j) is
+// numerators[j] / denominators[j], in lowest terms with positive denominators
+#[derive(Debug)]
+struct Polynomial {
+    numerators: Vec<BigInt>,
+    denominators: Vec<u64>,
+}
+
+impl Polynomial {
+    // a_n = sum over k of d_k * n(n - 1)...(n - k + 1) / k!, and expanding each falling factorial with the signed
+    // stirling numbers of the first kind gives the power basis. scaling everything by degree! keeps it integral
+    fn fit(seq: &[i64]) -> Self {
+        let differences = forward_differences(seq);
+        let Some(degree) = differences.len().checked_sub(1) else {
+            return Polynomial {
+                numerators: Vec::new(),
+                denominators: Vec::new(),
+            };
+        };
+        // degree! has to fit in a u64
+        assert!(degree <= 20, "degree {degree} is too high to fit");
+        let factorials = (0..=degree as u64)
+            .scan(1u64, |acc, e| {
+                *acc *= e.max(1);
+                Some(*acc)
+            })
+            .collect::<Vec<_>>();
+
+        // stirling[k][j] is s(k, j), n(n - 1)...(n - k + 1) = sum over j of s(k, j) n^j
+        let mut stirling = vec![vec![0i64; degree + 1]; degree + 1];
+        stirling[0][0] = 1;
+        for k in 0..degree {
+            for j in 0..=k + 1 {
+                let from_lower = if j > 0 { stirling[k][j - 1] } else { 0 };
+                let from_same = if j <= k { stirling[k][j] } else { 0 };
+                stirling[k + 1][j] = from_lower - k as i64 * from_same;
+            }
+        }
+
+        let scale = factorials[degree];
+        let (numerators, denominators) = (0..=degree)
+            .map(|j| {
+                let mut numerator = BigInt::from(0);
+                for (k, difference) in differences.iter().enumerate().skip(j) {
+                    numerator = numerator.add(
+                        &BigInt::from(*difference)
+                            .mul_small(stirling[k][j])
+                            .mul_small((scale / factorials[k]) as i64),
+                    );
+                }
+                let common = gcd(numerator.rem_small(scale), scale);
+                (numerator.div_exact_small(common), scale / common)
+            })
+            .unzip();
+        Polynomial {
+            numerators,
+            denominators,
+        }
+    }
+
+    // None for the zero polynomial
+    fn degree(&self) -> Option<usize> {
+        self.numerators.len().checked_sub(1)
+    }
+
+    // horner's method over a common denominator, so it's exact whenever p(n) is an integer
+    fn evaluate(&self, n: i64) -> BigInt {
+        let common = self
+            .denominators
+            .iter()
+            .fold(1, |acc, e| acc / gcd(acc, *e) * e);
+        let mut total = BigInt::from(0);
+        for (numerator, denominator) in self.numerators.iter().zip(&self.denominators).rev() {
+            total = total
+                .mul_small(n)
+                .add(&numerator.mul_small((common / denominator) as i64));
+        }
+        total.div_exact_small(common)
+    }
+}
+
+// highest power first, like `p(n) = 3/2 n^2 - n + 4`
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "p(n) =")?;
+        let mut first = true;
+        for (j, (numerator, denominator)) in self
+            .numerators
+            .iter()
+            .zip(&self.denominators)
+            .enumerate()
+            .rev()
+        {
+            if numerator.is_zero() {
+                continue;
+            }
+            let sign = match (first, numerator.negative) {
+                (true, true) => " -",
+                (true, false) => " ",
+                (false, true) => " - ",
+                (false, false) => " + ",
+            };
+            let magnitude = BigInt {
+                negative: false,
+                limbs: numerator.limbs.clone(),
+            };
+            let coefficient = match (*denominator, j) {
+                // a bare n rather than 1 n
+                (1, 1..) if magnitude == BigInt::from(1) => String::new(),
+                (1, 0) => format!("{magnitude}"),
+                (1, _) => format!("{magnitude} "),
+                (_, 0) => format!("{magnitude}/{denominator}"),
+                _ => format!("{magnitude}/{denominator} "),
+            };
+            let power = match j {
+                0 => String::new(),
+                1 => "n".to_string(),
+                _ => format!("n^{j}"),
+            };
+            write!(f, "{sign}{coefficient}{power}")?;
+            first = false;
+        }
+        if first {
+            write!(f, " 0")?;
+        }
+        Ok(())
+    }
+}
+
+// just enough of an arbitrary precision integer for far off terms. sign and magnitude, the magnitude in little
+// endian base 2^32 limbs without trailing zero limbs, so zero has no limbs
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+        BigInt {
+            negative: value < 0,
+            limbs: vec![magnitude as u32, (magnitude >> 32) as u32],
+        }
+        .trimmed()
+    }
+}
+
+impl BigInt {
+    fn trimmed(mut self) -> Self {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        if self.limbs.is_empty() {
+            self.negative = false;
+        }
+        self
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn cmp_magnitude(a: &[u32], b: &[u32]) -> std::cmp::Ordering {
+        a.len()
+            .cmp(&b.len())
+            .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+    }
+
+    fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+            let mut carry = 0u64;
+            for i in 0..self.limbs.len().max(other.limbs.len()) {
+                let sum = *self.limbs.get(i).unwrap_or(&0) as u64
+                    + *other.limbs.get(i).unwrap_or(&0) as u64
+                    + carry;
+                limbs.push(sum as u32);
+                carry = sum >> 32;
+            }
+            limbs.push(carry as u32);
+            return BigInt {
+                negative: self.negative,
+                limbs,
+            }
+            .trimmed();
+        }
+
+        // opposite signs, take the smaller magnitude from the bigger and keep the bigger one's sign
+        let (big, small) = match BigInt::cmp_magnitude(&self.limbs, &other.limbs) {
+            std::cmp::Ordering::Less => (other, self),
+            _ => (self, other),
+        };
+        let mut limbs = Vec::with_capacity(big.limbs.len());
+        let mut borrow = 0i64;
+        for i in 0..big.limbs.len() {
+            let mut difference =
+                big.limbs[i] as i64 - *small.limbs.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(difference as u32);
+        }
+        BigInt {
+            negative: big.negative,
+            limbs,
+        }
+        .trimmed()
+    }
+
+    fn mul_small(&self, factor: i64) -> BigInt {
+        let magnitude = factor.unsigned_abs() as u128;
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 2);
+        let mut carry = 0u128;
+        for limb in &self.limbs {
+            let product = *limb as u128 * magnitude + carry;
+            limbs.push(product as u32);
+            carry = product >> 32;
+        }
+        while carry != 0 {
+            limbs.push(carry as u32);
+            carry >>= 32;
+        }
+        BigInt {
+            negative: self.negative != (factor < 0),
+            limbs,
+        }
+        .trimmed()
+    }
+
+    // truncating division, the remainder is of the magnitude
+    fn div_rem_small(&self, divisor: u64) -> (BigInt, u64) {
+        let mut limbs = vec![0; self.limbs.len()];
+        let mut remainder = 0u128;
+        for i in (0..self.limbs.len()).rev() {
+            let current = (remainder << 32) | self.limbs[i] as u128;
+            limbs[i] = (current / divisor as u128) as u32;
+            remainder = current % divisor as u128;
+        }
+        let quotient = BigInt {
+            negative: self.negative,
+            limbs,
+        }
+        .trimmed();
+        (quotient, remainder as u64)
+    }
+
+    fn div_exact_small(&self, divisor: u64) -> BigInt {
+        let (quotient, remainder) = self.div_rem_small(divisor);
+        debug_assert_eq!(remainder, 0, "{self} isn't a multiple of {divisor}");
+        quotient
+    }
+
+    fn rem_small(&self, divisor: u64) -> u64 {
+        self.div_rem_small(divisor).1
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0u64, |acc, e| (acc << 32) | *e as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off 9 decimal digits at a time, least significant first
+        let mut chunks = Vec::new();
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, remainder) = rest.div_rem_small(1_000_000_000);
+            chunks.push(remainder);
+            rest = quotient;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.last().unwrap())?;
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(f, "{chunk:09}")?;
+        }
+        Ok(())
+    }
+}
 
 fn main() {
     use std::fs::File;
@@ -114,7 +431,7 @@ fn main() {
     while let Some(Ok(line)) = lines.next() {
         sequences.push(
             line.split_whitespace()
-                .map(|e| e.parse::<i32>().unwrap())
+                .map(|e| e.parse::<i64>().unwrap())
                 .collect::<Vec<_>>(),
         );
     }
@@ -122,10 +439,117 @@ fn main() {
     let mut sum_predicted = 0;
     let mut sum_prior = 0;
     for seq in &sequences {
-        // println!("{seq:?} {}\n", predict_next_term(seq));
-        sum_predicted += predict_next_term(seq);
-        let reversed = seq.iter().cloned().rev().collect::<Vec<_>>();
-        sum_prior += predict_next_term(&reversed);
+        let next = extrapolate(seq, 1, Direction::Forward)[0];
+        let previous = extrapolate(seq, 1, Direction::Backward)[0];
+        assert_eq!(
+            next as i32,
+            predict_next_term(&seq.iter().map(|e| *e as i32).collect::<Vec<_>>())
+        );
+        // the closed form has to agree just past either end
+        assert_eq!(evaluate_at(seq, seq.len() as i64).to_i64(), Some(next));
+        assert_eq!(evaluate_at(seq, -1).to_i64(), Some(previous));
+        let polynomial = Polynomial::fit(seq);
+        for (i, term) in seq.iter().enumerate() {
+            assert_eq!(polynomial.evaluate(i as i64).to_i64(), Some(*term));
+        }
+        assert_eq!(polynomial.evaluate(seq.len() as i64).to_i64(), Some(next));
+        sum_predicted += next;
+        sum_prior += previous;
     }
     println!("{sum_predicted}, {sum_prior}");
+
+    // --polynomials prints the polynomial behind every history
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.iter().any(|e| e == "--polynomials") {
+        for seq in &sequences {
+            let polynomial = Polynomial::fit(seq);
+            match polynomial.degree() {
+                Some(degree) => println!("degree {degree}: {polynomial}"),
+                None => println!("zero: {polynomial}"),
+            }
+        }
+    }
+
+    // --extrapolate k prints k more terms in both directions for every history, --at n the term at index n
+    if let Some(i) = args.iter().position(|e| e == "--extrapolate") {
+        let count = args[i + 1].parse().unwrap();
+        for seq in &sequences {
+            let mut before = extrapolate(seq, count, Direction::Backward);
+            before.reverse();
+            println!(
+                "{before:?} {seq:?} {:?}",
+                extrapolate(seq, count, Direction::Forward)
+            );
+        }
+    }
+    if let Some(i) = args.iter().position(|e| e == "--at") {
+        let n = args[i + 1].parse().unwrap();
+        let mut total = BigInt::from(0);
+        for seq in &sequences {
+            let term = evaluate_at(seq, n);
+            println!("{term}");
+            total = total.add(&term);
+        }
+        println!("sum of terms at {n}: {total}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_borrows_across_limbs() {
+        let two_to_32 = BigInt::from(1 << 32);
+        assert_eq!(two_to_32.add(&BigInt::from(-1)).to_string(), "4294967295");
+        assert_eq!(
+            BigInt::from(1).add(&BigInt::from(-(1 << 32))).to_string(),
+            "-4294967295"
+        );
+
+        // -2^64 + 1 borrows through two zero limbs
+        let minus_two_to_64 = two_to_32.mul_small(-(1 << 32));
+        assert_eq!(minus_two_to_64.to_string(), "-18446744073709551616");
+        assert_eq!(
+            minus_two_to_64.add(&BigInt::from(1)).to_string(),
+            "-18446744073709551615"
+        );
+        assert!(minus_two_to_64.add(&two_to_32.mul_small(1 << 32)).is_zero());
+    }
+
+    #[test]
+    fn evaluates_far_off_terms() {
+        let linear = [0, 3, 6, 9, 12];
+        let square = [0, 1, 4, 9, 16, 25];
+        let cube = [0, 1, 8, 27, 64];
+        let n = 1_000_000_000_000;
+        assert_eq!(evaluate_at(&linear, n).to_string(), "3000000000000");
+        assert_eq!(
+            evaluate_at(&square, n).to_string(),
+            "1000000000000000000000000"
+        );
+        assert_eq!(evaluate_at(&linear, -n).to_string(), "-3000000000000");
+        assert_eq!(
+            evaluate_at(&square, -n).to_string(),
+            "1000000000000000000000000"
+        );
+        assert_eq!(
+            evaluate_at(&cube, -n).to_string(),
+            "-1000000000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn prints_zero_and_negatives() {
+        assert_eq!(BigInt::from(0).to_string(), "0");
+        assert_eq!(BigInt::from(5).add(&BigInt::from(-5)).to_string(), "0");
+        assert_eq!(BigInt::from(-5).to_string(), "-5");
+        // the lower chunk keeps its leading zeros
+        assert_eq!(
+            BigInt::from(-1_000_000_000_000_000_007).to_string(),
+            "-1000000000000000007"
+        );
+        assert_eq!(BigInt::from(i64::MIN).to_string(), i64::MIN.to_string());
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+    }
 }